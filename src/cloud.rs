@@ -0,0 +1,8 @@
+pub mod interpolation;
+pub mod clustering;
+pub mod outlier;
+pub mod registration;
+pub mod downsampling;
+pub mod normals;
+pub mod metrics;
+mod linalg;
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use rayon::prelude::*;
+use crate::points::point::{Point, Point3D};
+use crate::tree::Ikd::IKDTree;
+use crate::tree::kdtree::KDTree;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterLabel {
+    Noise,
+    Cluster(usize),
+}
+
+/*
+Result of dbscan, clusters are numbered from 0 to cluster_count - 1.
+*/
+#[derive(Debug)]
+pub struct Clustering<'p, P> {
+    // Points of the tree, labels[i] is the label of points[i].
+    pub points: &'p [P],
+    pub labels: Vec<ClusterLabel>,
+    pub cluster_count: usize,
+}
+
+/**
+ DBSCAN over every point stored in tree.
+ @param
+    eps: neighbourhood radius, a point's neighbourhood includes the point itself.
+    min_points: a point with at least min_points neighbours is a core point.
+ Clusters grow from core points through neighbourhoods of other core points,
+ points reached by no cluster are Noise.
+ **/
+pub fn dbscan<'p, P>(tree: &'p KDTree<P>, eps: f32, min_points: usize) -> Clustering<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    let points = tree.points();
+
+    // Counting does not materialise neighbourhoods, only core points need them later.
+    let is_core: Vec<bool> = points.par_iter()
+        .map(|point| tree.count_within_radius(point, eps) >= min_points)
+        .collect();
+
+    let mut labels: Vec<Option<ClusterLabel>> = vec![None; points.len()];
+    let mut cluster_count = 0;
+
+    for start in 0..points.len() {
+        if labels[start].is_some() || !is_core[start] {
+            continue;
+        }
+
+        let cluster = ClusterLabel::Cluster(cluster_count);
+        cluster_count += 1;
+
+        labels[start] = Some(cluster);
+        let mut pending = VecDeque::from([start]);
+
+        while let Some(index) = pending.pop_front() {
+            // Border points join the cluster but do not expand it.
+            if !is_core[index] {
+                continue;
+            }
+
+            for (_, neighbour) in tree.within_radius(&points[index], eps) {
+                let neighbour_index = tree.index_of(neighbour);
+                // Points keep the first cluster reaching them, unreached ones become Noise at the end.
+                if labels[neighbour_index].is_none() {
+                    labels[neighbour_index] = Some(cluster);
+                    pending.push_back(neighbour_index);
+                }
+            }
+        }
+    }
+
+    let labels = labels.into_iter().map(|label| label.unwrap_or(ClusterLabel::Noise)).collect();
+
+    Clustering { points, labels, cluster_count }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label_of(clustering: &Clustering<Point3D>, point: &Point3D) -> ClusterLabel {
+        let index = clustering.points.iter().position(|p| p == point).unwrap();
+        clustering.labels[index]
+    }
+
+    #[test]
+    fn test_two_clusters_and_noise() {
+        let mut points = vec![];
+        for i in 0..10 {
+            points.push(Point3D::new(i as f32 * 0.25, 0.0, 0.0));
+            points.push(Point3D::new(10.0 + i as f32 * 0.25, 5.0, 0.0));
+        }
+        points.push(Point3D::new(50.0, 50.0, 50.0));
+
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let clustering = dbscan(&root, 0.3, 3);
+
+        assert_eq!(clustering.cluster_count, 2);
+        assert_eq!(clustering.labels.len(), 21);
+        // Labels line up with the points as create_kd_tree arranged them.
+        assert_eq!(clustering.points, points);
+        assert_eq!(label_of(&clustering, &Point3D::new(50.0, 50.0, 50.0)), ClusterLabel::Noise);
+
+        let first = label_of(&clustering, &Point3D::new(0.0, 0.0, 0.0));
+        let second = label_of(&clustering, &Point3D::new(10.0, 5.0, 0.0));
+        assert_ne!(first, second);
+        assert_eq!(label_of(&clustering, &Point3D::new(2.25, 0.0, 0.0)), first);
+        assert_eq!(clustering.labels.iter().filter(|label| **label == first).count(), 10);
+        assert_eq!(clustering.labels.iter().filter(|label| **label == second).count(), 10);
+    }
+
+    #[test]
+    fn test_border_point_joins_cluster() {
+        let mut points = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.1, 0.0, 0.0),
+            Point3D::new(-0.1, 0.0, 0.0),
+            // Only the core point at the origin reaches it.
+            Point3D::new(0.0, 0.9, 0.0),
+        ];
+
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let clustering = dbscan(&root, 1.0, 4);
+
+        assert_eq!(clustering.cluster_count, 1);
+        assert!(clustering.labels.iter().all(|label| *label == ClusterLabel::Cluster(0)));
+
+        let all_noise = dbscan(&root, 0.05, 2);
+        assert_eq!(all_noise.cluster_count, 0);
+        assert!(all_noise.labels.iter().all(|label| *label == ClusterLabel::Noise));
+    }
+}
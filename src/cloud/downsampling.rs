@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use crate::points::point::{Point, Point3D};
+use crate::tree::kdtree::KDTree;
+
+/* Point kept for each occupied voxel. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoxelRepresentative {
+    // Mean of the points of the voxel.
+    Centroid,
+    // First point of the voxel in input order.
+    First,
+    // Point of the voxel closest to the voxel centre.
+    NearestToCenter,
+}
+
+struct Voxel {
+    first: Point3D,
+    sum: [f64; 3],
+    count: usize,
+    nearest: Point3D,
+    nearest_distance: f32,
+}
+
+/**
+ Voxel-grid downsampling.
+ @param
+    voxel_size: edge length of the cubic voxels, aligned on the origin. A size that is
+    not strictly positive leaves the cloud untouched.
+    representative: which point stands for each occupied voxel.
+ @return one point per occupied voxel, in the order the voxels are first reached.
+ **/
+pub fn voxel_downsample(points: &[Point3D], voxel_size: f32, representative: VoxelRepresentative) -> Vec<Point3D> {
+    if voxel_size <= 0.0 || voxel_size.is_nan() {
+        return points.to_vec();
+    }
+
+    let mut voxel_index: HashMap<[i64; 3], usize> = HashMap::new();
+    let mut voxels: Vec<Voxel> = vec![];
+
+    for point in points {
+        let key = [point.x, point.y, point.z].map(|value| (value / voxel_size).floor() as i64);
+        let center = key.map(|cell| (cell as f32 + 0.5) * voxel_size);
+        let distance = point.distance_to(&Point3D::new(center[0], center[1], center[2]));
+
+        let index = *voxel_index.entry(key).or_insert_with(|| {
+            voxels.push(Voxel {
+                first: *point,
+                sum: [0.0; 3],
+                count: 0,
+                nearest: *point,
+                nearest_distance: f32::INFINITY,
+            });
+            voxels.len() - 1
+        });
+
+        let voxel = &mut voxels[index];
+        voxel.sum[0] += point.x as f64;
+        voxel.sum[1] += point.y as f64;
+        voxel.sum[2] += point.z as f64;
+        voxel.count += 1;
+        if distance < voxel.nearest_distance {
+            voxel.nearest = *point;
+            voxel.nearest_distance = distance;
+        }
+    }
+
+    voxels.iter()
+        .map(|voxel| match representative {
+            VoxelRepresentative::Centroid => {
+                let [x, y, z] = voxel.sum.map(|sum| (sum / voxel.count as f64) as f32);
+                Point3D::new(x, y, z)
+            },
+            VoxelRepresentative::First => voxel.first,
+            VoxelRepresentative::NearestToCenter => voxel.nearest,
+        })
+        .collect()
+}
+
+/* Same as voxel_downsample over every point stored in tree, First follows tree.points() order. */
+pub fn voxel_downsample_tree(tree: &KDTree<Point3D>, voxel_size: f32, representative: VoxelRepresentative) -> Vec<Point3D> {
+    voxel_downsample(tree.points(), voxel_size, representative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Ikd::IKDTree;
+
+    fn two_voxel_cloud() -> Vec<Point3D> {
+        vec![
+            Point3D::new(0.1, 0.1, 0.1),
+            Point3D::new(0.5, 0.5, 0.5),
+            Point3D::new(0.9, 0.6, 0.3),
+            Point3D::new(1.2, 0.2, 0.2),
+        ]
+    }
+
+    #[test]
+    fn test_representatives() {
+        let points = two_voxel_cloud();
+
+        let first = voxel_downsample(&points, 1.0, VoxelRepresentative::First);
+        assert_eq!(first, vec![Point3D::new(0.1, 0.1, 0.1), Point3D::new(1.2, 0.2, 0.2)]);
+
+        let nearest = voxel_downsample(&points, 1.0, VoxelRepresentative::NearestToCenter);
+        assert_eq!(nearest, vec![Point3D::new(0.5, 0.5, 0.5), Point3D::new(1.2, 0.2, 0.2)]);
+
+        let centroid = voxel_downsample(&points, 1.0, VoxelRepresentative::Centroid);
+        assert_eq!(centroid.len(), 2);
+        assert!(centroid[0].distance_to(&Point3D::new(0.5, 0.4, 0.3)) < 1e-6);
+        assert_eq!(centroid[1], Point3D::new(1.2, 0.2, 0.2));
+    }
+
+    #[test]
+    fn test_negative_coordinates_and_sizes() {
+        let points = vec![Point3D::new(-0.1, 0.0, 0.0), Point3D::new(0.1, 0.0, 0.0)];
+
+        // Cells are floored, so -0.1 and 0.1 fall into different voxels.
+        assert_eq!(voxel_downsample(&points, 1.0, VoxelRepresentative::First).len(), 2);
+        assert_eq!(voxel_downsample(&points, 0.0, VoxelRepresentative::First), points);
+    }
+
+    #[test]
+    fn test_voxel_downsample_tree() {
+        let mut points = Point3D::generate_points(5_000, 0.0, 4.0);
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let reduced = voxel_downsample_tree(&tree, 1.0, VoxelRepresentative::Centroid);
+        assert!(reduced.len() <= 5 * 5 * 5);
+        assert_eq!(reduced.len(), voxel_downsample(&points, 1.0, VoxelRepresentative::Centroid).len());
+
+        // create_kd_tree left points in tree order, so the first point of each voxel is the same.
+        let first = voxel_downsample_tree(&tree, 1.0, VoxelRepresentative::First);
+        assert_eq!(first, voxel_downsample(&points, 1.0, VoxelRepresentative::First));
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::Debug;
+use rayon::prelude::*;
+use crate::points::point::{Point, Point3D};
+use crate::tree::Ikd::IKDTree;
+use crate::tree::kdtree::KDTree;
+
+/*
+Inverse distance weighted interpolation
+=======================================
+Estimates a value at a query location from the neighbour_count nearest points
+of the tree, each weighted by 1 / distance^power. value_of reads the scalar
+carried by a stored point. Neighbours farther than radius, when set, are ignored.
+*/
+pub struct IdwInterpolator<'t, P, F> {
+    tree: &'t KDTree<P>,
+    value_of: F,
+    neighbour_count: usize,
+    power: f32,
+    radius: Option<f32>,
+}
+
+impl<'t, P, F> IdwInterpolator<'t, P, F>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync, F: Fn(&P) -> f32 + Sync
+{
+    /* Interpolator with power 2 and no radius cutoff. */
+    pub fn new(tree: &'t KDTree<P>, value_of: F, neighbour_count: usize) -> Self {
+        IdwInterpolator {
+            tree,
+            value_of,
+            neighbour_count,
+            power: 2.0,
+            radius: None,
+        }
+    }
+
+    pub fn with_power(mut self, power: f32) -> Self {
+        self.power = power;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = Some(radius);
+        self
+    }
+
+    /**
+     @return the interpolated value, None when no neighbour is within the radius.
+     **/
+    pub fn interpolate(&self, query_point: &P) -> Option<f32> {
+        let radius = self.radius.unwrap_or(f32::INFINITY);
+        let neighbours = self.tree.find_closest(query_point, self.tree.k(), self.neighbour_count)?;
+        let neighbours: Vec<&(f32, &P)> = neighbours.iter().take_while(|(distance, _)| *distance <= radius).collect();
+
+        if neighbours.is_empty() {
+            return None;
+        }
+
+        // A query on top of stored points takes their value, the weight would be infinite.
+        let exact_hits: Vec<f32> = neighbours.iter()
+            .filter(|(distance, _)| *distance == 0.0)
+            .map(|(_, point)| (self.value_of)(point))
+            .collect();
+        if !exact_hits.is_empty() {
+            return Some(exact_hits.iter().sum::<f32>() / exact_hits.len() as f32);
+        }
+
+        let mut weighted_sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (distance, point) in neighbours {
+            let weight = 1.0 / distance.powf(self.power);
+            weighted_sum += weight * (self.value_of)(point);
+            weight_sum += weight;
+        }
+
+        Some(weighted_sum / weight_sum)
+    }
+
+    /* Interpolates every query point in parallel, results keep the order of query_points. */
+    pub fn interpolate_batch(&self, query_points: &[P]) -> Vec<Option<f32>> {
+        query_points.par_iter()
+            .map(|query_point| self.interpolate(query_point))
+            .collect()
+    }
+}
+
+impl<'t, F> IdwInterpolator<'t, Point3D, F>
+    where F: Fn(&Point3D) -> f32 + Sync
+{
+    /**
+     Resamples onto a regular grid spanning [min_point, max_point].
+     @param
+        counts: amount of grid nodes along x, y and z, each at least 1.
+     @return values with x varying fastest, then y, then z.
+     **/
+    pub fn interpolate_grid(&self, min_point: &Point3D, max_point: &Point3D, counts: [usize; 3]) -> Vec<Option<f32>> {
+        self.interpolate_batch(&grid_points(min_point, max_point, counts))
+    }
+}
+
+/* Nodes of a regular grid, x varying fastest. A single node on an axis sits on min_point. */
+pub fn grid_points(min_point: &Point3D, max_point: &Point3D, counts: [usize; 3]) -> Vec<Point3D> {
+    let step = |min: f32, max: f32, count: usize| {
+        if count > 1 { (max - min) / (count - 1) as f32 } else { 0.0 }
+    };
+    let step_x = step(min_point.x, max_point.x, counts[0]);
+    let step_y = step(min_point.y, max_point.y, counts[1]);
+    let step_z = step(min_point.z, max_point.z, counts[2]);
+
+    let mut points = Vec::with_capacity(counts.iter().product());
+    for z in 0..counts[2] {
+        for y in 0..counts[1] {
+            for x in 0..counts[0] {
+                points.push(Point3D::new(
+                    min_point.x + x as f32 * step_x,
+                    min_point.y + y as f32 * step_y,
+                    min_point.z + z as f32 * step_z,
+                ));
+            }
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Terrain-like samples: the value is the height z.
+    fn height(point: &Point3D) -> f32 {
+        point.z
+    }
+
+    // Field sampled along the x axis.
+    fn field(point: &Point3D) -> f32 {
+        point.x * 10.0
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let mut points = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(2.0, 0.0, 0.0),
+            Point3D::new(10.0, 0.0, 0.0),
+        ];
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let interpolator = IdwInterpolator::new(&root, field, 2);
+
+        // Equal distances give the plain average of the two nearest values.
+        assert_eq!(interpolator.interpolate(&Point3D::new(1.0, 0.0, 0.0)), Some(10.0));
+
+        // Exact hits return the stored value.
+        assert_eq!(interpolator.interpolate(&Point3D::new(2.0, 0.0, 0.0)), Some(20.0));
+
+        // Closer points weigh more: weights 1/1 and 1/9 with power 2.
+        let value = interpolator.interpolate(&Point3D::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((value - (20.0 / 9.0) / (1.0 + 1.0 / 9.0)).abs() < 1e-5);
+
+        let power_one = IdwInterpolator::new(&root, field, 2).with_power(1.0);
+        let value = power_one.interpolate(&Point3D::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((value - (20.0 / 3.0) / (1.0 + 1.0 / 3.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_radius_cutoff() {
+        let mut points = vec![Point3D::new(0.0, 0.0, 0.0), Point3D::new(5.0, 0.0, 0.0)];
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let interpolator = IdwInterpolator::new(&root, field, 2).with_radius(2.0);
+
+        assert_eq!(interpolator.interpolate(&Point3D::new(0.5, 0.0, 0.0)), Some(0.0));
+        assert_eq!(interpolator.interpolate(&Point3D::new(20.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_interpolate_grid() {
+        let mut points = Point3D::generate_points(500, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let interpolator = IdwInterpolator::new(&root, height, 6);
+
+        let min_point = Point3D::new(1.0, 1.0, 5.0);
+        let max_point = Point3D::new(9.0, 5.0, 5.0);
+        let grid = grid_points(&min_point, &max_point, [5, 3, 1]);
+        assert_eq!(grid.len(), 15);
+        assert_eq!(grid[1], Point3D::new(3.0, 1.0, 5.0));
+        assert_eq!(grid[14], max_point);
+
+        let values = interpolator.interpolate_grid(&min_point, &max_point, [5, 3, 1]);
+        assert_eq!(values.len(), 15);
+        for (grid_point, value) in grid.iter().zip(&values) {
+            assert_eq!(*value, interpolator.interpolate(grid_point));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+/*
+Eigen decomposition of a small symmetric matrix with the cyclic Jacobi method.
+@return eigenvalues in increasing order and the matching unit eigenvectors,
+eigenvectors[i] belonging to eigenvalues[i].
+*/
+pub fn symmetric_eigen<const N: usize>(matrix: [[f64; N]; N]) -> ([f64; N], [[f64; N]; N]) {
+    let mut a = matrix;
+    let mut v = [[0.0; N]; N];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+
+    for _ in 0..64 {
+        let off_diagonal: f64 = (0..N)
+            .flat_map(|p| (p + 1..N).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum();
+        if off_diagonal < 1e-30 {
+            break;
+        }
+
+        for p in 0..N {
+            for q in p + 1..N {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+
+                // Rotation zeroing a[p][q].
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+                a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let mut order: [usize; N] = std::array::from_fn(|i| i);
+    order.sort_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j]));
+
+    let eigenvalues = std::array::from_fn(|i| a[order[i]][order[i]]);
+    let eigenvectors = std::array::from_fn(|i| std::array::from_fn(|k| v[k][order[i]]));
+    (eigenvalues, eigenvectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let matrix = [
+            [4.0, 1.0, 0.0],
+            [1.0, 3.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let (eigenvalues, eigenvectors) = symmetric_eigen(matrix);
+
+        assert!((eigenvalues[0] - 1.0).abs() < 1e-9);
+        assert!(eigenvalues[0] <= eigenvalues[1] && eigenvalues[1] <= eigenvalues[2]);
+
+        for (value, vector) in eigenvalues.iter().zip(&eigenvectors) {
+            for row in 0..3 {
+                let product: f64 = (0..3).map(|col| matrix[row][col] * vector[col]).sum();
+                assert!((product - value * vector[row]).abs() < 1e-9);
+            }
+            let length: f64 = vector.iter().map(|x| x * x).sum();
+            assert!((length - 1.0).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::Debug;
+use rayon::prelude::*;
+use crate::points::point::{Point, Point3D};
+use crate::tree::Ikd::IKDTree;
+use crate::tree::kdtree::KDTree;
+
+/*
+Distance of every point of a cloud to its nearest point in another cloud.
+*/
+#[derive(Debug)]
+pub struct Residuals<'p, P> {
+    // Points of the tree, distances[i] belongs to points[i].
+    pub points: &'p [P],
+    pub distances: Vec<f32>,
+}
+
+impl<'p, P> Residuals<'p, P> {
+    /* Directed Hausdorff distance, the worst residual. */
+    pub fn max(&self) -> f32 {
+        self.distances.iter().copied().fold(0.0, f32::max)
+    }
+
+    pub fn mean(&self) -> f32 {
+        if self.distances.is_empty() {
+            return 0.0;
+        }
+        self.distances.iter().sum::<f32>() / self.distances.len() as f32
+    }
+}
+
+/* Residuals in both directions between cloud a and cloud b. */
+#[derive(Debug)]
+pub struct CloudComparison<'a, 'b, P> {
+    pub a_to_b: Residuals<'a, P>,
+    pub b_to_a: Residuals<'b, P>,
+}
+
+impl<'a, 'b, P> CloudComparison<'a, 'b, P> {
+    /* Symmetric Hausdorff distance, the larger of both directed distances. */
+    pub fn hausdorff(&self) -> f32 {
+        self.a_to_b.max().max(self.b_to_a.max())
+    }
+
+    /* Chamfer distance, sum of the mean residuals of both directions. */
+    pub fn chamfer(&self) -> f32 {
+        self.a_to_b.mean() + self.b_to_a.mean()
+    }
+}
+
+/**
+ Nearest neighbour distance from every point stored in from to the points of to.
+ max() of the result is the directed Hausdorff distance from -> to.
+ **/
+pub fn residuals<'p, P>(from: &'p KDTree<P>, to: &KDTree<P>) -> Residuals<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    let points = from.points();
+
+    let distances = points.par_iter()
+        .map(|point| to.find_closest(point, to.k(), 1).map_or(f32::INFINITY, |nearest| nearest[0].0))
+        .collect();
+
+    Residuals { points, distances }
+}
+
+/**
+ Compares two clouds in one call, e.g. reconstructed vertices against a ground truth scan.
+ **/
+pub fn compare_clouds<'a, 'b, P>(cloud_a: &'a KDTree<P>, cloud_b: &'b KDTree<P>) -> CloudComparison<'a, 'b, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    CloudComparison {
+        a_to_b: residuals(cloud_a, cloud_b),
+        b_to_a: residuals(cloud_b, cloud_a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hausdorff_and_chamfer() {
+        let mut points_a = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+        ];
+        let mut points_b = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(4.0, 0.0, 0.0),
+        ];
+        let tree_a = KDTree::create_kd_tree(&mut points_a, 0, 3).unwrap();
+        let tree_b = KDTree::create_kd_tree(&mut points_b, 0, 3).unwrap();
+
+        let comparison = compare_clouds(&tree_a, &tree_b);
+
+        // Every point of a is in b, but the extra point of b is 3 away from a.
+        assert_eq!(comparison.a_to_b.max(), 0.0);
+        assert_eq!(comparison.b_to_a.max(), 3.0);
+        assert_eq!(comparison.hausdorff(), 3.0);
+        assert_eq!(comparison.chamfer(), 1.0);
+
+        let index = comparison.b_to_a.points.iter().position(|p| p.x == 4.0).unwrap();
+        assert_eq!(comparison.b_to_a.distances[index], 3.0);
+    }
+
+    #[test]
+    fn test_residuals_match_brute_force() {
+        let mut points_a = Point3D::generate_points(300, 0.0, 10.0);
+        let mut points_b = Point3D::generate_points(200, 2.0, 12.0);
+        let all_b = points_b.clone();
+        let tree_a = KDTree::create_kd_tree(&mut points_a, 0, 3).unwrap();
+        let tree_b = KDTree::create_kd_tree(&mut points_b, 0, 3).unwrap();
+
+        let result = residuals(&tree_a, &tree_b);
+        assert_eq!(result.points.len(), 300);
+        assert_eq!(result.points, points_a);
+
+        for (point, distance) in result.points.iter().zip(&result.distances) {
+            let expected = all_b.iter().map(|other| point.distance_to(other)).fold(f32::INFINITY, f32::min);
+            assert_eq!(*distance, expected);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use rayon::prelude::*;
+use crate::points::point::Point3D;
+use crate::tree::Ikd::IKDTree;
+use crate::tree::kdtree::KDTree;
+use super::linalg::symmetric_eigen;
+
+/* Neighbours used to fit the local plane. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Neighbourhood {
+    // The given amount of nearest points, the point itself included.
+    Nearest(usize),
+    // Every point within the radius, the point itself included.
+    Radius(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceNormal {
+    // Unit eigenvector of the smallest covariance eigenvalue.
+    pub normal: [f32; 3],
+    // Surface variation: smallest eigenvalue over the sum of eigenvalues, 0 on a plane.
+    pub curvature: f32,
+}
+
+/* Result of estimate_normals. */
+#[derive(Debug)]
+pub struct Normals<'p> {
+    // Points of the tree, normals[i] belongs to points[i].
+    pub points: &'p [Point3D],
+    // None when fewer than 3 neighbours were found.
+    pub normals: Vec<Option<SurfaceNormal>>,
+}
+
+/**
+ Normal and curvature of every point stored in tree, from the PCA of its neighbourhood.
+ @param
+    viewpoint: when set, normals are flipped to face it, otherwise their sign is arbitrary.
+ **/
+pub fn estimate_normals<'p>(
+    tree: &'p KDTree<Point3D>,
+    neighbourhood: Neighbourhood,
+    viewpoint: Option<&Point3D>
+) -> Normals<'p>
+{
+    let points = tree.points();
+
+    let normals = points.par_iter()
+        .map(|point| {
+            let mut surface_normal = normal_at(tree, point, neighbourhood)?;
+            if let Some(viewpoint) = viewpoint {
+                orient_towards(&mut surface_normal, point, viewpoint);
+            }
+            Some(surface_normal)
+        })
+        .collect();
+
+    Normals { points, normals }
+}
+
+/**
+ Normal and curvature of the surface around query_point, which does not need to be stored in tree.
+ @return None when fewer than 3 neighbours are found.
+ **/
+pub fn normal_at(tree: &KDTree<Point3D>, query_point: &Point3D, neighbourhood: Neighbourhood) -> Option<SurfaceNormal> {
+    let neighbours = match neighbourhood {
+        Neighbourhood::Nearest(count) => tree.find_closest(query_point, tree.k(), count).unwrap_or_default(),
+        Neighbourhood::Radius(radius) => tree.within_radius(query_point, radius),
+    };
+    if neighbours.len() < 3 {
+        return None;
+    }
+
+    let count = neighbours.len() as f64;
+    let mut centroid = [0.0f64; 3];
+    for (_, point) in &neighbours {
+        centroid[0] += point.x as f64 / count;
+        centroid[1] += point.y as f64 / count;
+        centroid[2] += point.z as f64 / count;
+    }
+
+    let mut covariance = [[0.0f64; 3]; 3];
+    for (_, point) in &neighbours {
+        let delta = [point.x as f64 - centroid[0], point.y as f64 - centroid[1], point.z as f64 - centroid[2]];
+        for row in 0..3 {
+            for col in 0..3 {
+                covariance[row][col] += delta[row] * delta[col] / count;
+            }
+        }
+    }
+
+    let (eigenvalues, eigenvectors) = symmetric_eigen(covariance);
+    let total: f64 = eigenvalues.iter().sum();
+    let curvature = if total > 0.0 { (eigenvalues[0].max(0.0) / total) as f32 } else { 0.0 };
+
+    Some(SurfaceNormal {
+        normal: eigenvectors[0].map(|value| value as f32),
+        curvature,
+    })
+}
+
+fn orient_towards(surface_normal: &mut SurfaceNormal, point: &Point3D, viewpoint: &Point3D) {
+    let to_viewpoint = [viewpoint.x - point.x, viewpoint.y - point.y, viewpoint.z - point.z];
+    let dot: f32 = surface_normal.normal.iter().zip(to_viewpoint).map(|(n, v)| n * v).sum();
+    if dot < 0.0 {
+        surface_normal.normal = surface_normal.normal.map(|value| -value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plane_z(height: f32) -> Vec<Point3D> {
+        let mut points = vec![];
+        for x in 0..10 {
+            for y in 0..10 {
+                points.push(Point3D::new(x as f32 * 0.5, y as f32 * 0.5, height));
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_plane_normals() {
+        let mut points = plane_z(2.0);
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let viewpoint = Point3D::new(0.0, 0.0, 10.0);
+        let result = estimate_normals(&tree, Neighbourhood::Nearest(8), Some(&viewpoint));
+        assert_eq!(result.normals.len(), 100);
+        assert_eq!(result.points, points);
+
+        for normal in &result.normals {
+            let normal = normal.unwrap();
+            assert!((normal.normal[2] - 1.0).abs() < 1e-4);
+            assert!(normal.curvature < 1e-6);
+        }
+
+        // Looking from below flips every normal.
+        let below = Point3D::new(0.0, 0.0, -10.0);
+        let result = estimate_normals(&tree, Neighbourhood::Radius(0.8), Some(&below));
+        assert!(result.normals.iter().all(|normal| normal.unwrap().normal[2] < -0.999));
+    }
+
+    #[test]
+    fn test_curvature_and_small_neighbourhoods() {
+        let mut points = plane_z(0.0);
+        points.extend(plane_z(0.0).iter().map(|p| Point3D::new(p.x, 0.0, p.y + 0.5)));
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        // Around the fold the neighbourhood spans two planes.
+        let fold = normal_at(&tree, &Point3D::new(2.0, 0.0, 0.0), Neighbourhood::Radius(1.0)).unwrap();
+        let flat = normal_at(&tree, &Point3D::new(2.0, 4.0, 0.0), Neighbourhood::Radius(1.0)).unwrap();
+        assert!(fold.curvature > flat.curvature);
+
+        assert!(normal_at(&tree, &Point3D::new(50.0, 50.0, 50.0), Neighbourhood::Radius(1.0)).is_none());
+        assert!(normal_at(&tree, &Point3D::new(2.0, 4.0, 0.0), Neighbourhood::Nearest(2)).is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Debug;
+use crate::points::point::{Point, Point3D};
+use crate::tree::Ikd::IKDTree;
+use crate::tree::graph::KnnGraph;
+use crate::tree::kdtree::KDTree;
+
+/*
+Point of the cloud together with its position in the caller's input, so results computed in
+tree order can be given back in input order. Geometry is the one of point.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct IndexedPoint<P> {
+    index: usize,
+    point: P,
+}
+
+impl<P> Point<Point3D> for IndexedPoint<P>
+    where P: Point<Point3D>
+{
+    fn generate_points(amount: usize, min: f32, max: f32) -> Vec<Point3D> {
+        P::generate_points(amount, min, max)
+    }
+
+    fn distance_to(&self, other: &Self) -> f32 {
+        self.point.distance_to(&other.point)
+    }
+
+    fn random_point(min: f32, max: f32) -> Point3D {
+        P::random_point(min, max)
+    }
+
+    fn get_coordinate(&self) -> Vec<&f32> {
+        self.point.get_coordinate()
+    }
+
+    fn get_coordinate_at(&self, axis: usize) -> f32 {
+        self.point.get_coordinate_at(axis)
+    }
+}
+
+/*
+Statistical outlier analysis of a kNN graph, entries are aligned with graph.points.
+*/
+#[derive(Debug)]
+pub struct StatisticalOutliers {
+    // Mean distance of each point to its neighbours.
+    pub mean_distances: Vec<f32>,
+    // mean + std_ratio * stddev of mean_distances.
+    pub threshold: f32,
+    pub is_outlier: Vec<bool>,
+}
+
+/**
+ PCL-style statistical outlier detection.
+ @param
+    graph: kNN graph of the cloud, its neighbour count is the k of the analysis.
+    std_ratio: points whose mean neighbour distance is beyond mean + std_ratio * stddev are outliers.
+ **/
+pub fn statistical_outliers<P>(graph: &KnnGraph<P>, std_ratio: f32) -> StatisticalOutliers
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    let mean_distances: Vec<f32> = (0..graph.len())
+        .map(|index| {
+            let (_, distances) = graph.neighbours_of(index);
+            if distances.is_empty() { 0.0 } else { distances.iter().sum::<f32>() / distances.len() as f32 }
+        })
+        .collect();
+
+    let count = mean_distances.len().max(1) as f32;
+    let mean = mean_distances.iter().sum::<f32>() / count;
+    let variance = mean_distances.iter().map(|distance| (distance - mean).powi(2)).sum::<f32>() / count;
+    let threshold = mean + std_ratio * variance.sqrt();
+
+    let is_outlier = mean_distances.iter().map(|distance| *distance > threshold).collect();
+
+    StatisticalOutliers { mean_distances, threshold, is_outlier }
+}
+
+/**
+ Local Outlier Factor of every point of graph, aligned with graph.points.
+ Around 1 for points as dense as their neighbours, clearly above 1 for outliers.
+ **/
+pub fn local_outlier_factor<P>(graph: &KnnGraph<P>) -> Vec<f32>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    // Distance to the farthest of the k neighbours.
+    let k_distances: Vec<f32> = (0..graph.len())
+        .map(|index| graph.neighbours_of(index).1.last().copied().unwrap_or(0.0))
+        .collect();
+
+    // Local reachability density, infinite when every neighbour is a duplicate.
+    let densities: Vec<f32> = (0..graph.len())
+        .map(|index| {
+            let (neighbours, distances) = graph.neighbours_of(index);
+            let reach_sum: f32 = neighbours.iter()
+                .zip(distances)
+                .map(|(neighbour, distance)| k_distances[*neighbour].max(*distance))
+                .sum();
+
+            if reach_sum > 0.0 { neighbours.len() as f32 / reach_sum } else { f32::INFINITY }
+        })
+        .collect();
+
+    (0..graph.len())
+        .map(|index| {
+            let (neighbours, _) = graph.neighbours_of(index);
+            if neighbours.is_empty() || densities[index].is_infinite() {
+                return 1.0;
+            }
+
+            let neighbour_density = neighbours.iter().map(|neighbour| densities[*neighbour]).sum::<f32>()
+                / neighbours.len() as f32;
+            neighbour_density / densities[index]
+        })
+        .collect()
+}
+
+/**
+ Statistical outlier removal, meant to clean a cloud before indexing it.
+ @param
+    neighbour_count: k of the kNN graph.
+    std_ratio: see statistical_outliers.
+ @return the inliers, in the same order as points.
+ **/
+pub fn remove_statistical_outliers<P>(points: &[P], neighbour_count: usize, std_ratio: f32) -> Vec<P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    let mut working_points: Vec<IndexedPoint<P>> = points.iter()
+        .enumerate()
+        .map(|(index, point)| IndexedPoint { index, point: *point })
+        .collect();
+    let k = match points.first() {
+        Some(point) => point.get_coordinate().len(),
+        None => return vec![],
+    };
+
+    let tree = match KDTree::create_kd_tree(&mut working_points, 0, k) {
+        Ok(tree) => tree,
+        Err(_) => return vec![],
+    };
+    let graph = KnnGraph::from_tree(&tree, neighbour_count);
+    let outliers = statistical_outliers(&graph, std_ratio);
+
+    // The graph lists points in tree order, the mask is rebuilt in input order.
+    let mut is_outlier = vec![false; points.len()];
+    for (indexed, outlier) in graph.points.iter().zip(&outliers.is_outlier) {
+        is_outlier[indexed.index] = *outlier;
+    }
+
+    points.iter()
+        .zip(is_outlier)
+        .filter(|(_, is_outlier)| !*is_outlier)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cloud_with_outlier() -> Vec<Point3D> {
+        let mut points = vec![];
+        for x in 0..5 {
+            for y in 0..5 {
+                points.push(Point3D::new(x as f32, y as f32, 0.0));
+            }
+        }
+        points.push(Point3D::new(30.0, 30.0, 30.0));
+        points
+    }
+
+    #[test]
+    fn test_statistical_outliers() {
+        let mut points = cloud_with_outlier();
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let graph = KnnGraph::from_tree(&tree, 4);
+        let outliers = statistical_outliers(&graph, 1.0);
+
+        assert_eq!(outliers.is_outlier.iter().filter(|is_outlier| **is_outlier).count(), 1);
+        let index = outliers.is_outlier.iter().position(|is_outlier| *is_outlier).unwrap();
+        assert_eq!(graph.points[index], Point3D::new(30.0, 30.0, 30.0));
+        assert!(outliers.mean_distances[index] > outliers.threshold);
+    }
+
+    #[test]
+    fn test_local_outlier_factor() {
+        let mut points = cloud_with_outlier();
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let graph = KnnGraph::from_tree(&tree, 4);
+        let factors = local_outlier_factor(&graph);
+
+        for (point, factor) in graph.points.iter().zip(&factors) {
+            if point.x == 30.0 {
+                assert!(*factor > 5.0);
+            } else {
+                assert!(*factor < 2.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_statistical_outliers() {
+        let points = cloud_with_outlier();
+        let inliers = remove_statistical_outliers(&points, 4, 1.0);
+
+        assert_eq!(inliers.len(), 25);
+        assert!(!inliers.contains(&Point3D::new(30.0, 30.0, 30.0)));
+
+        // Inliers keep the order of the input, whatever order the tree stored them in.
+        let mut reversed = points.clone();
+        reversed.reverse();
+        assert_eq!(inliers, &points[..25]);
+        assert_eq!(remove_statistical_outliers(&reversed, 4, 1.0), &reversed[1..]);
+        assert!(remove_statistical_outliers::<Point3D>(&[], 4, 1.0).is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::points::point::{Point, Point3D};
+use crate::tree::Ikd::IKDTree;
+use crate::tree::kdtree::KDTree;
+use super::linalg::symmetric_eigen;
+
+/* Rotation followed by translation: p' = rotation * p + translation. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RigidTransform {
+    pub rotation: [[f32; 3]; 3],
+    pub translation: [f32; 3],
+}
+
+impl RigidTransform {
+    pub fn identity() -> Self {
+        RigidTransform {
+            rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            translation: [0.0; 3],
+        }
+    }
+
+    pub fn apply(&self, point: &Point3D) -> Point3D {
+        let coord = [point.x, point.y, point.z];
+        let moved: [f32; 3] = std::array::from_fn(|row| {
+            (0..3).map(|col| self.rotation[row][col] * coord[col]).sum::<f32>() + self.translation[row]
+        });
+        Point3D::new(moved[0], moved[1], moved[2])
+    }
+
+    /* Transform applying self first, then next. */
+    pub fn then(&self, next: &RigidTransform) -> Self {
+        let rotation = std::array::from_fn(|row| std::array::from_fn(|col| {
+            (0..3).map(|k| next.rotation[row][k] * self.rotation[k][col]).sum()
+        }));
+        let translation = std::array::from_fn(|row| {
+            (0..3).map(|k| next.rotation[row][k] * self.translation[k]).sum::<f32>() + next.translation[row]
+        });
+        RigidTransform { rotation, translation }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IcpIteration {
+    // RMS distance of the correspondences found in this iteration, before the update.
+    pub rms_error: f32,
+    pub correspondences: usize,
+}
+
+#[derive(Debug)]
+pub struct IcpResult {
+    // Maps the source cloud onto the target cloud.
+    pub transform: RigidTransform,
+    // RMS nearest neighbour distance of the source under transform.
+    pub rms_error: f32,
+    pub iterations: Vec<IcpIteration>,
+    pub converged: bool,
+}
+
+/**
+ Point-to-point Iterative Closest Point.
+ @param
+    source: cloud to move.
+    target: tree of the reference cloud.
+    max_iterations: upper bound of matching and alignment rounds.
+    tolerance: stops once the RMS error improves by less than this between two rounds.
+ **/
+pub fn icp(source: &[Point3D], target: &KDTree<Point3D>, max_iterations: usize, tolerance: f32) -> IcpResult {
+    let mut transform = RigidTransform::identity();
+    let mut iterations = Vec::with_capacity(max_iterations);
+    let mut converged = false;
+
+    // Nothing to align, the identity is returned without matching any point.
+    if source.is_empty() {
+        return IcpResult { transform, rms_error: 0.0, iterations, converged };
+    }
+
+    for _ in 0..max_iterations {
+        let moved: Vec<Point3D> = source.iter().map(|point| transform.apply(point)).collect();
+        let (matches, rms_error) = correspondences(&moved, target);
+
+        let previous_error = iterations.last().map(|iteration: &IcpIteration| iteration.rms_error);
+        iterations.push(IcpIteration { rms_error, correspondences: matches.len() });
+
+        if let Some(previous_error) = previous_error {
+            if (previous_error - rms_error).abs() < tolerance {
+                converged = true;
+                break;
+            }
+        }
+
+        transform = transform.then(&best_fit(&moved, &matches));
+    }
+
+    let moved: Vec<Point3D> = source.iter().map(|point| transform.apply(point)).collect();
+    let (_, rms_error) = correspondences(&moved, target);
+
+    IcpResult { transform, rms_error, iterations, converged }
+}
+
+/* Nearest target point of every moved source point, and their RMS distance. */
+fn correspondences(moved: &[Point3D], target: &KDTree<Point3D>) -> (Vec<Point3D>, f32) {
+    let nearest = target.find_closest_batch(moved, 1);
+
+    let mut matches = Vec::with_capacity(moved.len());
+    let mut squared_sum = 0.0;
+    for result in nearest.into_iter().flatten() {
+        let (distance, point) = result[0];
+        squared_sum += distance * distance;
+        matches.push(*point);
+    }
+
+    let rms_error = (squared_sum / matches.len().max(1) as f32).sqrt();
+    (matches, rms_error)
+}
+
+/*
+Rigid transform minimising the squared distances between source[i] and target[i],
+using Horn's closed form: the rotation is the quaternion eigenvector of the
+largest eigenvalue of a 4x4 matrix built from the cross-covariance.
+A single pair does not determine a rotation, only the translation between the
+centroids is returned then.
+*/
+fn best_fit(source: &[Point3D], target: &[Point3D]) -> RigidTransform {
+    let centroid = |points: &[Point3D]| {
+        let count = points.len().max(1) as f64;
+        let mut sum = [0.0f64; 3];
+        for point in points {
+            for (axis, value) in point.get_coordinate().into_iter().enumerate() {
+                sum[axis] += *value as f64;
+            }
+        }
+        sum.map(|value| value / count)
+    };
+    let source_centroid = centroid(source);
+    let target_centroid = centroid(target);
+
+    if source.len().min(target.len()) < 2 {
+        let translation = std::array::from_fn(|axis| (target_centroid[axis] - source_centroid[axis]) as f32);
+        return RigidTransform { translation, ..RigidTransform::identity() };
+    }
+
+    // s[i][j] = sum of centred source i-coordinate times centred target j-coordinate.
+    let mut s = [[0.0f64; 3]; 3];
+    for (a, b) in source.iter().zip(target) {
+        let a = [a.x as f64 - source_centroid[0], a.y as f64 - source_centroid[1], a.z as f64 - source_centroid[2]];
+        let b = [b.x as f64 - target_centroid[0], b.y as f64 - target_centroid[1], b.z as f64 - target_centroid[2]];
+        for i in 0..3 {
+            for j in 0..3 {
+                s[i][j] += a[i] * b[j];
+            }
+        }
+    }
+
+    let n = [
+        [s[0][0] + s[1][1] + s[2][2], s[1][2] - s[2][1], s[2][0] - s[0][2], s[0][1] - s[1][0]],
+        [s[1][2] - s[2][1], s[0][0] - s[1][1] - s[2][2], s[0][1] + s[1][0], s[2][0] + s[0][2]],
+        [s[2][0] - s[0][2], s[0][1] + s[1][0], -s[0][0] + s[1][1] - s[2][2], s[1][2] + s[2][1]],
+        [s[0][1] - s[1][0], s[2][0] + s[0][2], s[1][2] + s[2][1], -s[0][0] - s[1][1] + s[2][2]],
+    ];
+    let (_, eigenvectors) = symmetric_eigen(n);
+    let [w, x, y, z] = eigenvectors[3];
+
+    let rotation64 = [
+        [w * w + x * x - y * y - z * z, 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+        [2.0 * (x * y + w * z), w * w - x * x + y * y - z * z, 2.0 * (y * z - w * x)],
+        [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), w * w - x * x - y * y + z * z],
+    ];
+
+    let rotation = rotation64.map(|row| row.map(|value| value as f32));
+    let translation = std::array::from_fn(|row| {
+        (target_centroid[row] - (0..3).map(|col| rotation64[row][col] * source_centroid[col]).sum::<f64>()) as f32
+    });
+
+    RigidTransform { rotation, translation }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotation_z(angle: f32) -> RigidTransform {
+        let (sin, cos) = angle.sin_cos();
+        RigidTransform {
+            rotation: [[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]],
+            translation: [0.3, -0.2, 0.1],
+        }
+    }
+
+    #[test]
+    fn test_best_fit_recovers_transform() {
+        let source = Point3D::generate_points(50, 0.0, 10.0);
+        let expected = rotation_z(0.7);
+        let target: Vec<Point3D> = source.iter().map(|point| expected.apply(point)).collect();
+
+        let found = best_fit(&source, &target);
+        for row in 0..3 {
+            assert!((found.translation[row] - expected.translation[row]).abs() < 1e-3);
+            for col in 0..3 {
+                assert!((found.rotation[row][col] - expected.rotation[row][col]).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_icp_aligns_clouds() {
+        let mut target_points = Point3D::generate_points(1_000, 0.0, 10.0);
+        let misalignment = rotation_z(0.05);
+        let source: Vec<Point3D> = target_points.iter().map(|point| misalignment.apply(point)).collect();
+        let target = KDTree::create_kd_tree(&mut target_points, 0, 3).unwrap();
+
+        let result = icp(&source, &target, 50, 1e-6);
+
+        assert!(!result.iterations.is_empty());
+        assert!(result.rms_error < 1e-2);
+        assert!(result.rms_error < result.iterations[0].rms_error);
+        assert_eq!(result.iterations[0].correspondences, source.len());
+
+        // Undoing the misalignment brings every point back onto itself.
+        let restored = result.transform.apply(&misalignment.apply(&Point3D::new(5.0, 5.0, 5.0)));
+        assert!(restored.distance_to(&Point3D::new(5.0, 5.0, 5.0)) < 1e-2);
+    }
+
+    #[test]
+    fn test_icp_without_enough_points() {
+        let mut target_points = Point3D::generate_points(100, 0.0, 10.0);
+        let target = KDTree::create_kd_tree(&mut target_points, 0, 3).unwrap();
+
+        let result = icp(&[], &target, 10, 1e-6);
+        assert_eq!(result.transform, RigidTransform::identity());
+        assert!(result.iterations.is_empty());
+        assert!(!result.converged);
+
+        // One or two pairs still have to be moved onto their targets.
+        let source = [Point3D::new(1.0, 1.0, 1.0), Point3D::new(2.0, 3.0, 4.0)];
+        let displaced = rotation_z(0.7);
+        let target = source.map(|point| displaced.apply(&point));
+        for count in [1, 2] {
+            let fit = best_fit(&source[..count], &target[..count]);
+            for (point, expected) in source[..count].iter().zip(&target) {
+                assert!(fit.apply(point).distance_to(expected) < 1e-4);
+            }
+        }
+        assert_eq!(best_fit(&[], &[]), RigidTransform::identity());
+    }
+
+    #[test]
+    fn test_then_composes_in_order() {
+        let first = rotation_z(0.4);
+        let second = rotation_z(-1.1);
+        let point = Point3D::new(1.0, 2.0, 3.0);
+
+        let composed = first.then(&second).apply(&point);
+        let step_by_step = second.apply(&first.apply(&point));
+        assert!(composed.distance_to(&step_by_step) < 1e-5);
+        assert_eq!(RigidTransform::identity().apply(&point), point);
+    }
+}
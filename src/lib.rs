@@ -1,6 +1,18 @@
 mod points;
 mod tree;
+mod cloud;
 
 pub use points::point::{Point3D, Point};
-pub use tree::kdtree::KDTree;
-pub use tree::Ikd::IKDTree;
\ No newline at end of file
+pub use points::line::Line;
+pub use tree::kdtree::{KDTree, Node};
+pub use tree::graph::KnnGraph;
+pub use tree::dual::{closest_pair, closest_pairs, join_within};
+pub use tree::Ikd::{IKDTree, IIterator};
+pub use tree::iterator::{NearestIterator, NodeVisit, TraversalIterator, TraversalOrder};
+pub use cloud::interpolation::{IdwInterpolator, grid_points};
+pub use cloud::clustering::{dbscan, ClusterLabel, Clustering};
+pub use cloud::outlier::{local_outlier_factor, remove_statistical_outliers, statistical_outliers, StatisticalOutliers};
+pub use cloud::registration::{icp, IcpIteration, IcpResult, RigidTransform};
+pub use cloud::downsampling::{voxel_downsample, voxel_downsample_tree, VoxelRepresentative};
+pub use cloud::normals::{estimate_normals, normal_at, Neighbourhood, Normals, SurfaceNormal};
+pub use cloud::metrics::{compare_clouds, residuals, CloudComparison, Residuals};
\ No newline at end of file
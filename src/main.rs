@@ -1,6 +1,76 @@
-use fnp::{KDTree, Point3D, IKDTree, Point};
-use criterion::{criterion_group, criterion_main, Criterion};
-use std::time::{Duration, Instant};
+use fnp::{KDTree, KnnGraph, Point3D, IKDTree, Point};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::time::Instant;
+
+/*
+Baseline for bench_create_kd_tree: the arrangement create_kd_tree used before median selection,
+which sorted every range on its axis at every level.
+*/
+fn sort_per_level(points: &mut [Point3D], k: usize, depth: usize) {
+    if points.is_empty() {
+        return;
+    }
+
+    let axis = depth % k;
+    points.sort_by(|a, b| KDTree::sorting_point(a, b, axis).unwrap());
+
+    let median = points.len() / 2;
+    let (left_points, right_points) = points.split_at_mut(median);
+    sort_per_level(left_points, k, depth + 1);
+    sort_per_level(&mut right_points[1..], k, depth + 1);
+}
+
+fn bench_create_kd_tree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_kd_tree");
+    group.sample_size(10);
+
+    for amount in [100_000, 1_000_000] {
+        let points = Point3D::generate_points(amount, 1.0, 2.0);
+
+        // Every iteration arranges the same unarranged copy of the points. Storing them
+        // afterwards costs the same with both arrangements, so it is left out.
+        group.bench_function(format!("{} points, median selection", amount), |b| {
+            b.iter_batched(
+                || points.clone(),
+                |mut points| KDTree::build_kd_tree(&mut points, 3, 0),
+                BatchSize::LargeInput
+            )
+        });
+
+        group.bench_function(format!("{} points, sort per level", amount), |b| {
+            b.iter_batched(
+                || points.clone(),
+                |mut points| sort_per_level(&mut points, 3, 0),
+                BatchSize::LargeInput
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_knn_graph(c: &mut Criterion) {
+    let mut group = c.benchmark_group("knn_graph");
+    group.sample_size(10);
+
+    let mut points = Point3D::generate_points(100_000, 1.0, 2.0);
+    let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+    group.bench_function("100000 points, 8 neighbours, from_tree", |b| {
+        b.iter(|| KnnGraph::from_tree(&root, 8))
+    });
+
+    // What a caller would write without the graph: one query per point, one after another.
+    group.bench_function("100000 points, 8 neighbours, per-point loop", |b| {
+        b.iter(|| {
+            root.points().iter()
+                .map(|point| root.find_closest_excluding(point, 8, point))
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.finish();
+}
 
 fn bench_find_closest(c: &mut Criterion) {
     let mut group = c.benchmark_group("find_closest");
@@ -40,8 +110,17 @@ fn bench_find_closest(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("k = 3, limit = 10, epsilon = 0.5", |b| {
+        b.iter(|| root.find_closest_approx(&query_point, 10, 0.5))
+    });
+
+    let batch_queries = Point3D::generate_points(1_000, 1.0, 1.6);
+    group.bench_function("batch of 1000 queries, limit = 10", |b| {
+        b.iter(|| root.find_closest_batch(&batch_queries, 10))
+    });
+
     group.finish();
 }
 
-criterion_group!(benches, bench_find_closest);
+criterion_group!(benches, bench_create_kd_tree, bench_knn_graph, bench_find_closest);
 criterion_main!(benches);
\ No newline at end of file
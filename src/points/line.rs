@@ -0,0 +1,144 @@
+use crate::points::point::{Point, Point3D};
+
+/*
+Ray, line segment or infinite line, stored as origin + t * direction with t
+limited to [t_min, t_max].
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    origin: Vec<f32>,
+    direction: Vec<f32>,
+    t_min: f32,
+    t_max: f32,
+}
+
+impl Line {
+    /* Half-line starting at origin and going along direction. */
+    pub fn ray<P: Point<Point3D>>(origin: &P, direction: &P) -> Self {
+        Line::new(origin, &Self::coordinate(direction), 0.0, f32::INFINITY)
+    }
+
+    /* Segment between start and end, both included. */
+    pub fn segment<P: Point<Point3D>>(start: &P, end: &P) -> Self {
+        let direction = Self::coordinate(end).iter()
+            .zip(start.get_coordinate())
+            .map(|(e, s)| e - s)
+            .collect::<Vec<f32>>();
+        Line::new(start, &direction, 0.0, 1.0)
+    }
+
+    /* Infinite line going through point along direction. */
+    pub fn infinite<P: Point<Point3D>>(point: &P, direction: &P) -> Self {
+        Line::new(point, &Self::coordinate(direction), f32::NEG_INFINITY, f32::INFINITY)
+    }
+
+    fn new<P: Point<Point3D>>(origin: &P, direction: &[f32], t_min: f32, t_max: f32) -> Self {
+        Line {
+            origin: Self::coordinate(origin),
+            direction: direction.to_vec(),
+            t_min,
+            t_max,
+        }
+    }
+
+    fn coordinate<P: Point<Point3D>>(point: &P) -> Vec<f32> {
+        point.get_coordinate().into_iter().copied().collect()
+    }
+
+    /* Euclidean distance from coordinate to the closest point of the line. */
+    pub fn distance_to(&self, coordinate: &[f32]) -> f32 {
+        self.distance_by(|axis| coordinate[axis])
+    }
+
+    /* Same as distance_to, the coordinates of point are read in place. */
+    pub fn distance_to_point<P: Point<Point3D>>(&self, point: &P) -> f32 {
+        self.distance_by(|axis| point.get_coordinate_at(axis))
+    }
+
+    /* Distance to the coordinate whose value on each axis is value_at(axis), nothing is collected. */
+    pub(crate) fn distance_by<F: Fn(usize) -> f32>(&self, value_at: F) -> f32 {
+        let length_sq: f32 = self.direction.iter().map(|d| d * d).sum();
+
+        // A zero direction leaves only the origin.
+        let t = if length_sq > 0.0 {
+            let projection: f32 = (0..self.origin.len())
+                .map(|axis| (value_at(axis) - self.origin[axis]) * self.direction[axis])
+                .sum();
+            (projection / length_sq).clamp(self.t_min, self.t_max)
+        } else {
+            0.0
+        };
+
+        let mut distance_sq = 0.0;
+        for axis in 0..self.origin.len() {
+            let delta = self.origin[axis] + t * self.direction[axis] - value_at(axis);
+            distance_sq += delta * delta;
+        }
+        distance_sq.sqrt()
+    }
+
+    /*
+    Slab test against the box [min_coord, max_coord] grown by margin on every side.
+    A miss means every point of the box is farther than margin from the line.
+    */
+    pub fn hits_box(&self, min_coord: &[f32], max_coord: &[f32], margin: f32) -> bool {
+        let mut t_enter = self.t_min;
+        let mut t_exit = self.t_max;
+
+        for axis in 0..self.origin.len() {
+            let low = min_coord[axis] - margin;
+            let high = max_coord[axis] + margin;
+
+            if self.direction[axis] == 0.0 {
+                if self.origin[axis] < low || self.origin[axis] > high {
+                    return false;
+                }
+                continue;
+            }
+
+            let t_low = (low - self.origin[axis]) / self.direction[axis];
+            let t_high = (high - self.origin[axis]) / self.direction[axis];
+            t_enter = t_enter.max(t_low.min(t_high));
+            t_exit = t_exit.min(t_low.max(t_high));
+
+            if t_enter > t_exit {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_to() {
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        let direction = Point3D::new(1.0, 0.0, 0.0);
+
+        let ray = Line::ray(&origin, &direction);
+        assert_eq!(ray.distance_to(&[5.0, 3.0, 0.0]), 3.0);
+        assert_eq!(ray.distance_to(&[-4.0, 3.0, 0.0]), 5.0);
+
+        let line = Line::infinite(&origin, &direction);
+        assert_eq!(line.distance_to(&[-4.0, 3.0, 0.0]), 3.0);
+
+        let segment = Line::segment(&origin, &Point3D::new(2.0, 0.0, 0.0));
+        assert_eq!(segment.distance_to(&[1.0, 0.0, 2.0]), 2.0);
+        assert_eq!(segment.distance_to(&[5.0, 4.0, 0.0]), 5.0);
+        assert_eq!(segment.distance_to_point(&Point3D::new(5.0, 4.0, 0.0)), 5.0);
+    }
+
+    #[test]
+    fn test_hits_box() {
+        let ray = Line::ray(&Point3D::new(0.0, 0.0, 0.0), &Point3D::new(1.0, 1.0, 0.0));
+
+        assert!(ray.hits_box(&[2.0, 2.0, -1.0], &[3.0, 3.0, 1.0], 0.0));
+        assert!(!ray.hits_box(&[-3.0, -3.0, -1.0], &[-2.0, -2.0, 1.0], 0.0));
+        assert!(!ray.hits_box(&[2.0, 0.0, -1.0], &[3.0, 0.5, 1.0], 0.0));
+        assert!(ray.hits_box(&[2.0, 0.0, -1.0], &[3.0, 0.5, 1.0], 1.0));
+    }
+}
@@ -5,6 +5,11 @@ pub trait Point<T> {
     fn random_point(min: f32, max: f32) -> T;
 
     fn get_coordinate(&self) -> Vec<&f32>;
+
+    /* Single coordinate of the point, without collecting all of them. */
+    fn get_coordinate_at(&self, axis: usize) -> f32 {
+        *self.get_coordinate()[axis]
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -59,11 +64,20 @@ impl Point<Point3D> for Point3D
     fn get_coordinate(&self) -> Vec<&f32> {
         vec![&self.x, &self.y, &self.z]
     }
+
+    fn get_coordinate_at(&self, axis: usize) -> f32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("Point3D has no axis {}", axis),
+        }
+    }
 }
 
 impl PartialEq for Point3D {
     fn eq(&self, other: &Self) -> bool {
-        self.x == other.x && self.y == other.y && self.z == self.z
+        self.x == other.x && self.y == other.y && self.z == other.z
     }
 }
 
@@ -83,7 +97,7 @@ mod tests {
     #[test]
     fn test_generate_points() {
         let amount = 5;
-        let points = Point3D::generate_points(amount);
+        let points = Point3D::generate_points(amount, 1.0, 2.0);
         assert_eq!(points.len(), amount);
         for point in &points {
             assert!(point.x.is_finite());
@@ -93,6 +107,15 @@ mod tests {
     }
 
     #[test]
+    fn test_get_coordinate_at() {
+        let p = Point3D::new(1.2, 3.4, 5.6);
+        for axis in 0..3 {
+            assert_eq!(p.get_coordinate_at(axis), *p.get_coordinate()[axis]);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision)]
     fn test_distance_to() {
         let p1 = Point3D::new(1.0, 2.0, 3.0);
         let p2 = Point3D::new(4.0, 5.0, 6.0);
@@ -1,3 +1,11 @@
+#[allow(non_snake_case)]
 pub mod Ikd;
 pub mod kdtree;
-mod error_handler;
\ No newline at end of file
+pub mod graph;
+pub mod iterator;
+pub mod dual;
+mod error_handler;
+mod queue;
+mod bounds;
+#[cfg(test)]
+mod test_helpers;
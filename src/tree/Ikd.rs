@@ -1,7 +1,10 @@
 use std::cmp::Ordering;
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::points::line::Line;
 use crate::tree::error_handler::ComparisonError;
+use crate::tree::iterator::{NearestIterator, TraversalIterator, TraversalOrder};
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq)]
 pub enum NodeDirection {
     LEFT,
@@ -27,18 +30,26 @@ pub trait IKDTree<P>
         depth: is used to calculate the axis which is used to compare dimension .
         k: is the dimension .
      **/
-    fn create_kd_tree(points: &mut Vec<P>, depth: usize, k: usize) -> Result<Rc<Self::Output>, String>;
+    fn create_kd_tree(points: &mut Vec<P>, depth: usize, k: usize) -> Result<Arc<Self::Output>, String>;
 
+    /**
+     Arranges points in place so that the median of every range splits it on the
+     axis of its depth, the middle point of the whole slice is the root.
+     **/
     fn build_kd_tree
     (
-        init_kd_tree: Self::Output,
-        points: &mut Vec<P>,
+        points: &mut [P],
         k: usize,
         depth: usize,
-    ) -> Option<Rc<Self::Output>>;
+    );
 
-    // .........
-    fn init() -> Self::Output;
+    /**
+     Same as create_kd_tree, but takes ownership of points instead of arranging the caller's.
+     @param
+        points: in any order, they are arranged by build_kd_tree before being stored.
+     Empty points or a k outside 1..=dimension are rejected like in create_kd_tree.
+     **/
+    fn init(points: Vec<P>, k: usize, depth: usize) -> Result<Self::Output, String>;
 
     fn sorting_point(
         point_a: &P,
@@ -51,12 +62,13 @@ pub trait IKDTree<P>
         n_point_b: &(f32, &P),
     ) -> Result<Ordering, ComparisonError>;
 
-    fn operation_point_list(
-        points: &Vec<P>,
-        median: usize,
-        direction: NodeDirection
-    ) -> &[P];
-
+    /**
+     k nearest neighbour search.
+     @param
+        k: ignored, kept for compatibility. The search splits on the k the tree was built with.
+        point_limit: is the amount of nearest points to return.
+     @return points sorted by distance, None when point_limit is 0.
+     **/
     fn find_closest(
         &self,
         query_point: &P,
@@ -64,21 +76,146 @@ pub trait IKDTree<P>
         point_limit: usize
     ) -> Option<Vec<(f32, &P)>>;
 
-    fn nearest_neighbour
-    <'p>
-    (
-        node: &'p Self::Output,
-        max_distance_sq: f32,
+    /**
+     Same as find_closest, but only points accepted by filter are returned.
+     Rejected points are skipped during the search, so up to point_limit accepted
+     points are still found.
+     **/
+    fn find_closest_where<F>(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        filter: F
+    ) -> Option<Vec<(f32, &P)>>
+        where F: Fn(&P) -> bool;
+
+    /**
+     Same as find_closest, but never returns the stored point excluded.
+     @param
+        excluded: reference to a point stored in this tree, e.g. taken from traverse or from a
+        previous result. It is matched by address, so duplicates of it are still returned.
+     Passing the same stored point as query_point and excluded gives its nearest other points.
+     **/
+    fn find_closest_excluding(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        excluded: &P
+    ) -> Option<Vec<(f32, &P)>>;
+
+    /**
+     Answers find_closest for every query point in parallel on all CPU cores.
+     @return one result per query point, in the same order as queries.
+     **/
+    fn find_closest_batch(
+        &self,
+        queries: &[P],
+        point_limit: usize
+    ) -> Vec<Option<Vec<(f32, &P)>>>
+        where P: Send + Sync;
+
+    /**
+     Approximate version of find_closest, the search splits on the k the tree was built with.
+     @param
+        epsilon: the i-th returned distance is at most (1 + epsilon) times the exact i-th
+        nearest distance. 0 gives the exact result, bigger values visit fewer nodes.
+     **/
+    fn find_closest_approx(
+        &self,
         query_point: &P,
-        best_points: Vec<(f32, &'p P)>,
-        k: usize
-    ) -> Vec<(f32, &'p P)>;
+        point_limit: usize,
+        epsilon: f32
+    ) -> Option<Vec<(f32, &P)>>;
 
     fn direction(query_point: &P, node_point: &P, axis: usize) -> NodeDirection;
+
+    /**
+     Fixed-distance search.
+     @param
+        query_point: is the center of the search sphere.
+        radius: every point whose distance_to the query point is at most radius is returned.
+     @return points sorted by distance, empty when nothing is inside the radius.
+     **/
+    fn within_radius(&self, query_point: &P, radius: f32) -> Vec<(f32, &P)>;
+
+    /**
+     Amount of points within_radius would return, without collecting them.
+     **/
+    fn count_within_radius(&self, query_point: &P, radius: f32) -> usize;
+
+    /**
+     Amount of points within_box would return, without collecting them.
+     **/
+    fn count_in_box(&self, min_point: &P, max_point: &P) -> usize;
+
+    /**
+     k nearest neighbour search against a ray, a segment or an infinite line instead of a point.
+     @param
+        line: built with Line::ray, Line::segment or Line::infinite.
+        point_limit: is the amount of nearest points to return.
+     @return points sorted by their distance to the line.
+     **/
+    fn find_closest_to_line(&self, line: &Line, point_limit: usize) -> Option<Vec<(f32, &P)>>;
+
+    /**
+     Opposite of find_closest.
+     @param
+        point_limit: is the amount of farthest points to return.
+     @return points sorted by decreasing distance.
+     **/
+    fn find_farthest(&self, query_point: &P, point_limit: usize) -> Option<Vec<(f32, &P)>>;
+
+    /**
+     Best-bin-first search, nodes are explored in order of their distance to the query point.
+     @param
+        point_limit: is the amount of nearest points to keep.
+        max_visits: the search stops after this many visited nodes. Every node holds one point,
+        so it is also the number of distance computations.
+     @return the best points found within the budget, exact when the budget is large enough.
+     **/
+    fn find_closest_bbf(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        max_visits: usize
+    ) -> Option<Vec<(f32, &P)>>;
+
+    /**
+     Axis-aligned range search.
+     @param
+        min_point: is the lower corner of the box.
+        max_point: is the upper corner of the box, bounds are inclusive on every axis.
+     @return every point inside the box, in no particular order.
+     **/
+    fn within_box(&self, min_point: &P, max_point: &P) -> Vec<&P>;
+
+    /**
+     Same search as within_box, but hands each point inside the box to visitor
+     instead of collecting them.
+     **/
+    fn visit_box<'p, F>(&'p self, min_point: &P, max_point: &P, visitor: F)
+        where F: FnMut(&'p P), P: 'p;
+
+    /**
+     Lazy nearest neighbour search.
+     @return an iterator yielding every point of the tree in increasing distance
+     from query_point, nodes are only expanded when the next point is requested.
+     **/
+    fn nearest_iter(&self, query_point: &P) -> NearestIterator<'_, P>;
+
+    /**
+     Walks the nodes of the tree.
+     @param
+        order: PreOrder, InOrder, LevelOrder (breadth-first) or Leaves (pre-order, leaves only).
+     @return an iterator yielding the point, depth and split axis of every visited node.
+     **/
+    fn traverse(&self, order: TraversalOrder) -> TraversalIterator<'_, P>;
 }
 
 /*Interface Iterator */
 pub trait IIterator {
-    fn get_next();
-    fn has_more() -> bool;
+    type Item;
+
+    fn get_next(&mut self) -> Option<Self::Item>;
+    fn has_more(&self) -> bool;
 }
\ No newline at end of file
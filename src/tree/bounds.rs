@@ -0,0 +1,96 @@
+use crate::points::point::{Point, Point3D};
+
+/*
+Axis-aligned bounding box of the points stored in a subtree, borrowed from the
+flat bounds array of the tree.
+Distances are Euclidean, the same metric Point3D::distance_to uses.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds<'b> {
+    pub min: &'b [f32],
+    pub max: &'b [f32],
+}
+
+impl<'b> Bounds<'b> {
+    /* Distance from coordinate to the closest point of the box, 0 when inside. */
+    pub fn min_distance(&self, coordinate: &[f32]) -> f32 {
+        self.min_distance_by(|axis| coordinate[axis])
+    }
+
+    /* Same as min_distance, the coordinates of point are read in place. */
+    pub fn min_distance_to_point<P: Point<Point3D>>(&self, point: &P) -> f32 {
+        self.min_distance_by(|axis| point.get_coordinate_at(axis))
+    }
+
+    fn min_distance_by<F: Fn(usize) -> f32>(&self, value_at: F) -> f32 {
+        let mut distance_sq = 0.0;
+        for axis in 0..self.min.len() {
+            let value = value_at(axis);
+            let delta = (self.min[axis] - value).max(value - self.max[axis]).max(0.0);
+            distance_sq += delta * delta;
+        }
+        distance_sq.sqrt()
+    }
+
+    /* Smallest distance between a point of this box and a point of other. */
+    pub fn distance_to_bounds(&self, other: &Bounds) -> f32 {
+        let mut distance_sq = 0.0;
+        for axis in 0..self.min.len() {
+            let delta = (other.min[axis] - self.max[axis]).max(self.min[axis] - other.max[axis]).max(0.0);
+            distance_sq += delta * delta;
+        }
+        distance_sq.sqrt()
+    }
+
+    /* Distance from coordinate to the farthest corner of the box. */
+    pub fn max_distance(&self, coordinate: &[f32]) -> f32 {
+        let mut distance_sq = 0.0;
+        for (axis, value) in coordinate.iter().enumerate() {
+            let delta = (value - self.min[axis]).abs().max((self.max[axis] - value).abs());
+            distance_sq += delta * delta;
+        }
+        distance_sq.sqrt()
+    }
+}
+
+impl<'b> Bounds<'b> {
+    /* Whether the whole box lies inside [min_coord, max_coord]. */
+    pub fn is_inside_box(&self, min_coord: &[f32], max_coord: &[f32]) -> bool {
+        (0..self.min.len()).all(|axis| min_coord[axis] <= self.min[axis] && self.max[axis] <= max_coord[axis])
+    }
+
+    /* Whether the box shares at least one point with [min_coord, max_coord]. */
+    pub fn intersects_box(&self, min_coord: &[f32], max_coord: &[f32]) -> bool {
+        (0..self.min.len()).all(|axis| min_coord[axis] <= self.max[axis] && self.min[axis] <= max_coord[axis])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distances() {
+        let bounds = Bounds { min: &[0.0, 0.0, 0.0], max: &[2.0, 1.0, 0.0] };
+
+        assert_eq!(bounds.min_distance(&[1.0, 0.5, 0.0]), 0.0);
+        assert_eq!(bounds.min_distance(&[5.0, 0.5, 4.0]), 5.0);
+        assert_eq!(bounds.max_distance(&[-1.0, 1.0, 0.0]), 10f32.sqrt());
+        assert_eq!(bounds.min_distance_to_point(&Point3D::new(5.0, 0.5, 4.0)), 5.0);
+    }
+
+    #[test]
+    fn test_box_relations() {
+        let bounds = Bounds { min: &[1.0, 1.0, 1.0], max: &[2.0, 2.0, 2.0] };
+
+        assert!(bounds.is_inside_box(&[0.0, 0.0, 0.0], &[2.0, 2.0, 2.0]));
+        assert!(!bounds.is_inside_box(&[1.5, 0.0, 0.0], &[3.0, 3.0, 3.0]));
+        assert!(bounds.intersects_box(&[1.5, 0.0, 0.0], &[3.0, 3.0, 3.0]));
+        assert!(!bounds.intersects_box(&[2.5, 0.0, 0.0], &[3.0, 3.0, 3.0]));
+
+        let other = Bounds { min: &[5.0, 6.0, 1.5], max: &[6.0, 7.0, 3.0] };
+        assert_eq!(bounds.distance_to_bounds(&other), 5.0);
+        assert_eq!(other.distance_to_bounds(&bounds), 5.0);
+        assert_eq!(bounds.distance_to_bounds(&bounds), 0.0);
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt::Debug;
+use crate::points::point::{Point, Point3D};
+use super::bounds::Bounds;
+use super::kdtree::{KDTree, Node};
+
+/*
+Candidate pairs between two trees, sorted by distance and never longer than pair_limit.
+*/
+struct PairSearch<'a, 'b, P> {
+    pairs: Vec<(f32, &'a P, &'b P)>,
+    pair_limit: usize,
+}
+
+impl<'a, 'b, P> PairSearch<'a, 'b, P> {
+    fn worst_distance(&self) -> f32 {
+        match self.pairs.last() {
+            Some((distance, _, _)) if self.pairs.len() >= self.pair_limit => *distance,
+            _ => f32::INFINITY
+        }
+    }
+
+    fn offer(&mut self, distance: f32, point_a: &'a P, point_b: &'b P) {
+        if distance < self.worst_distance() {
+            let position = self.pairs.partition_point(|(other, _, _)| *other <= distance);
+            self.pairs.insert(position, (distance, point_a, point_b));
+            self.pairs.truncate(self.pair_limit);
+        }
+    }
+}
+
+/**
+ Closest pair between the points of tree_a and the points of tree_b.
+ @return (distance, point of tree_a, point of tree_b).
+ **/
+pub fn closest_pair<'a, 'b, P>(tree_a: &'a KDTree<P>, tree_b: &'b KDTree<P>) -> Option<(f32, &'a P, &'b P)>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    closest_pairs(tree_a, tree_b, 1).into_iter().next()
+}
+
+/**
+ The pair_limit closest pairs between the points of tree_a and the points of tree_b.
+ Both trees are walked together, node pairs whose bounding boxes are farther apart
+ than the worst pair found so far are pruned.
+ @return pairs sorted by distance.
+ **/
+pub fn closest_pairs<'a, 'b, P>(
+    tree_a: &'a KDTree<P>,
+    tree_b: &'b KDTree<P>,
+    pair_limit: usize
+) -> Vec<(f32, &'a P, &'b P)>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    let mut search = PairSearch { pairs: Vec::with_capacity(pair_limit + 1), pair_limit };
+    if pair_limit > 0 {
+        dual_search(tree_a.root(), tree_b.root(), &mut search);
+    }
+    search.pairs
+}
+
+/*
+Every pair of subtree_a x subtree_b is covered once: the point of the bigger
+node is matched against the whole other subtree, then each of its children
+is paired with the other subtree.
+*/
+fn dual_search<'a, 'b, P>(node_a: Node<'a, P>, node_b: Node<'b, P>, search: &mut PairSearch<'a, 'b, P>)
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    if node_a.bounds().distance_to_bounds(&node_b.bounds()) >= search.worst_distance() {
+        return;
+    }
+
+    if node_a.size() >= node_b.size() {
+        let point_a = node_a.point();
+        point_search(point_a, node_b, search, &|point_b| (point_a, point_b));
+
+        for child_a in nearer_first(node_a, &node_b.bounds()) {
+            dual_search(child_a, node_b, search);
+        }
+    } else {
+        let point_b = node_b.point();
+        point_search(point_b, node_a, search, &|point_a| (point_a, point_b));
+
+        for child_b in nearer_first(node_b, &node_a.bounds()) {
+            dual_search(node_a, child_b, search);
+        }
+    }
+}
+
+/*
+Matches a single point against every point of a subtree, pair orders the two
+points as (point of tree_a, point of tree_b).
+*/
+fn point_search<'t, 'a, 'b, P, F>(
+    point: &P,
+    node: Node<'t, P>,
+    search: &mut PairSearch<'a, 'b, P>,
+    pair: &F
+)
+    where P: Point<Point3D> + Copy + PartialEq + Debug, F: Fn(&'t P) -> (&'a P, &'b P)
+{
+    if node.bounds().min_distance_to_point(point) >= search.worst_distance() {
+        return;
+    }
+
+    let node_point = node.point();
+    let (point_a, point_b) = pair(node_point);
+    search.offer(point.distance_to(node_point), point_a, point_b);
+
+    for child in node.children() {
+        point_search(point, child, search, pair);
+    }
+}
+
+/**
+ Spatial join of two trees, streams every pair (point of tree_a, point of tree_b)
+ whose distance is at most radius to emit as (distance, point_a, point_b).
+ Pairs come in no particular order.
+ **/
+pub fn join_within<'a, 'b, P, F>(tree_a: &'a KDTree<P>, tree_b: &'b KDTree<P>, radius: f32, mut emit: F)
+    where P: Point<Point3D> + Copy + PartialEq + Debug, F: FnMut(f32, &'a P, &'b P)
+{
+    dual_join(tree_a.root(), tree_b.root(), radius, &mut emit);
+}
+
+/* Same decomposition as dual_search, with a fixed radius instead of a shrinking one. */
+fn dual_join<'a, 'b, P, F>(node_a: Node<'a, P>, node_b: Node<'b, P>, radius: f32, emit: &mut F)
+    where P: Point<Point3D> + Copy + PartialEq + Debug, F: FnMut(f32, &'a P, &'b P)
+{
+    if node_a.bounds().distance_to_bounds(&node_b.bounds()) > radius {
+        return;
+    }
+
+    if node_a.size() >= node_b.size() {
+        let point_a = node_a.point();
+        point_join(point_a, node_b, radius, &mut |distance, point_b| emit(distance, point_a, point_b));
+
+        for child_a in node_a.children() {
+            dual_join(child_a, node_b, radius, emit);
+        }
+    } else {
+        let point_b = node_b.point();
+        point_join(point_b, node_a, radius, &mut |distance, point_a| emit(distance, point_a, point_b));
+
+        for child_b in node_b.children() {
+            dual_join(node_a, child_b, radius, emit);
+        }
+    }
+}
+
+fn point_join<'t, P, F>(point: &P, node: Node<'t, P>, radius: f32, emit: &mut F)
+    where P: Point<Point3D> + Copy + PartialEq + Debug, F: FnMut(f32, &'t P)
+{
+    if node.bounds().min_distance_to_point(point) > radius {
+        return;
+    }
+
+    let node_point = node.point();
+    let distance = point.distance_to(node_point);
+    if distance <= radius {
+        emit(distance, node_point);
+    }
+
+    for child in node.children() {
+        point_join(point, child, radius, emit);
+    }
+}
+
+/* Children of node, the one closer to bounds first. */
+fn nearer_first<'t, P>(node: Node<'t, P>, bounds: &Bounds) -> impl Iterator<Item = Node<'t, P>> {
+    let mut children = [node.left(), node.right()];
+    if let [Some(left_node), Some(right_node)] = children {
+        if right_node.bounds().distance_to_bounds(bounds) < left_node.bounds().distance_to_bounds(bounds) {
+            children.swap(0, 1);
+        }
+    }
+    children.into_iter().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Ikd::IKDTree;
+    use crate::tree::test_helpers::sorted;
+
+    #[test]
+    fn test_closest_pairs_match_brute_force() {
+        let mut points_a = Point3D::generate_points(400, 0.0, 10.0);
+        let mut points_b = Point3D::generate_points(300, 5.0, 15.0);
+        let all_a = points_a.clone();
+        let all_b = points_b.clone();
+        let tree_a = KDTree::create_kd_tree(&mut points_a, 0, 3).unwrap();
+        let tree_b = KDTree::create_kd_tree(&mut points_b, 0, 3).unwrap();
+
+        let expected = sorted(all_a.iter()
+            .flat_map(|a| all_b.iter().map(move |b| a.distance_to(b)))
+            .collect());
+
+        let pairs = closest_pairs(&tree_a, &tree_b, 20);
+        let distances: Vec<f32> = pairs.iter().map(|(distance, _, _)| *distance).collect();
+        assert_eq!(distances, &expected[..20]);
+
+        for (distance, point_a, point_b) in &pairs {
+            assert!(all_a.contains(point_a));
+            assert!(all_b.contains(point_b));
+            assert_eq!(point_a.distance_to(point_b), *distance);
+        }
+    }
+
+    #[test]
+    fn test_closest_pair() {
+        let mut points_a = vec![Point3D::new(0.0, 0.0, 0.0), Point3D::new(5.0, 5.0, 5.0)];
+        let mut points_b = vec![
+            Point3D::new(9.0, 9.0, 9.0),
+            Point3D::new(6.0, 5.0, 5.0),
+            Point3D::new(-3.0, 0.0, 0.0),
+        ];
+        let tree_a = KDTree::create_kd_tree(&mut points_a, 0, 3).unwrap();
+        let tree_b = KDTree::create_kd_tree(&mut points_b, 0, 3).unwrap();
+
+        let (distance, point_a, point_b) = closest_pair(&tree_a, &tree_b).unwrap();
+        assert_eq!(distance, 1.0);
+        assert_eq!(point_a, &Point3D::new(5.0, 5.0, 5.0));
+        assert_eq!(point_b, &Point3D::new(6.0, 5.0, 5.0));
+
+        assert!(closest_pairs(&tree_a, &tree_b, 0).is_empty());
+        assert_eq!(closest_pairs(&tree_a, &tree_b, 10).len(), 6);
+    }
+
+    #[test]
+    fn test_join_within() {
+        let mut points_a = Point3D::generate_points(400, 0.0, 10.0);
+        let mut points_b = Point3D::generate_points(300, 5.0, 15.0);
+        let all_a = points_a.clone();
+        let all_b = points_b.clone();
+        let tree_a = KDTree::create_kd_tree(&mut points_a, 0, 3).unwrap();
+        let tree_b = KDTree::create_kd_tree(&mut points_b, 0, 3).unwrap();
+
+        let radius = 1.0;
+        let expected = sorted(all_a.iter()
+            .flat_map(|a| all_b.iter().map(move |b| a.distance_to(b)))
+            .filter(|distance| *distance <= radius)
+            .collect());
+
+        let mut found = vec![];
+        join_within(&tree_a, &tree_b, radius, |distance, point_a, point_b| {
+            assert_eq!(point_a.distance_to(point_b), distance);
+            found.push(distance);
+        });
+
+        assert_eq!(sorted(found), expected);
+
+        let mut count = 0;
+        join_within(&tree_a, &tree_b, 100.0, |_, _, _| count += 1);
+        assert_eq!(count, all_a.len() * all_b.len());
+    }
+}
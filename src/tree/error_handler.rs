@@ -1,9 +1,5 @@
-use std::cmp::Ordering;
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use crate::Point3D;
-use crate::points::point::Point;
-use crate::tree::Ikd::NodeDirection;
 
 #[derive(Debug)]
 pub enum ComparisonError {
@@ -0,0 +1,302 @@
+use std::fmt::Debug;
+use std::ops::Range;
+use rayon::prelude::*;
+use crate::points::point::{Point, Point3D};
+use super::bounds::Bounds;
+use super::kdtree::{KDTree, Node};
+
+// Subtrees of at most this many points answer their queries with one shared traversal.
+const BLOCK_SIZE: usize = 32;
+
+/*
+k nearest neighbour graph of every point stored in a KDTree.
+Vertex i is points[i], which borrows tree.points(), so indices line up with the points create_kd_tree arranged.
+Adjacency is kept in compressed form: neighbours of point i are
+neighbours[offsets[i]..offsets[i + 1]], sorted by distance, with the
+matching distances at the same positions in distances.
+*/
+#[derive(Debug)]
+pub struct KnnGraph<'p, P> {
+    pub points: &'p [P],
+    pub offsets: Vec<usize>,
+    pub neighbours: Vec<usize>,
+    pub distances: Vec<f32>,
+}
+
+/*
+Neighbour search of a block of queries, the points of tree.points()[range].
+Query i is the point at range.start + i, its candidates are
+candidates[i * neighbour_count..][..lengths[i]], (squared distance, index) pairs sorted by distance.
+*/
+struct BlockSearch<'p, P> {
+    queries: &'p [P],
+    first_index: usize,
+    // Coordinates of the queries axis after axis, queries.len() values per axis.
+    coords: Vec<f32>,
+    dimension: usize,
+    min: Vec<f32>,
+    max: Vec<f32>,
+    middle: Vec<f32>,
+    candidates: Vec<(f32, usize)>,
+    lengths: Vec<usize>,
+    // Squared distance a new point has to beat to enter the candidates of each query.
+    worst: Vec<f32>,
+    // Distance beyond which no query of the block can improve.
+    bound: f32,
+    // Squared distances of the queries to the point being visited.
+    distances: Vec<f32>,
+    neighbour_count: usize,
+}
+
+impl<'p, P> KnnGraph<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug + Send + Sync
+{
+    /**
+     @param
+        tree: every point stored in the tree becomes a vertex of the graph.
+        neighbour_count: amount of nearest other points linked to each point, at most the
+        amount of other points in the tree.
+     A point is never its own neighbour, duplicates of it are.
+     Nearby points are queried together: every small subtree walks the tree once for all of
+     its points, a branch is left as soon as none of them can improve below it, and each
+     visited point is measured against the whole block at once.
+     Blocks run in parallel.
+     **/
+    pub fn from_tree(tree: &'p KDTree<P>, neighbour_count: usize) -> Self {
+        let points = tree.points();
+        // Every candidate buffer is sized by neighbour_count, a point has no more neighbours than this.
+        let neighbour_count = neighbour_count.min(points.len() - 1);
+
+        let mut blocks = vec![];
+        if neighbour_count > 0 {
+            query_blocks(tree.root(), &mut blocks);
+        }
+        // Blocks cover disjoint ranges, in range order their queries follow tree.points().
+        blocks.sort_by_key(|range| range.start);
+
+        let searches: Vec<BlockSearch<P>> = blocks.into_par_iter()
+            .map(|range| BlockSearch::new(tree, range, neighbour_count).run(tree.root()))
+            .collect();
+
+        let mut offsets = Vec::with_capacity(points.len() + 1);
+        let mut neighbours = Vec::with_capacity(points.len() * neighbour_count);
+        let mut distances = Vec::with_capacity(points.len() * neighbour_count);
+
+        offsets.push(0);
+        for search in &searches {
+            for query in 0..search.queries.len() {
+                for (_, other) in search.candidates_of(query) {
+                    neighbours.push(*other);
+                    distances.push(search.queries[query].distance_to(&points[*other]));
+                }
+                offsets.push(neighbours.len());
+            }
+        }
+        // Without neighbours no block was searched, every point still gets an empty range.
+        offsets.resize(points.len() + 1, 0);
+
+        KnnGraph { points, offsets, neighbours, distances }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /* Neighbour indices of the point at index and their distances. */
+    pub fn neighbours_of(&self, index: usize) -> (&[usize], &[f32]) {
+        let range = self.offsets[index]..self.offsets[index + 1];
+        (&self.neighbours[range.clone()], &self.distances[range])
+    }
+}
+
+/*
+Splits the tree into blocks of queries: small subtrees as a whole, and the point of every
+node above them on its own, so each block stays spatially tight.
+*/
+fn query_blocks<P>(node: Node<'_, P>, blocks: &mut Vec<Range<usize>>) {
+    if node.size() <= BLOCK_SIZE {
+        blocks.push(node.range());
+        return;
+    }
+
+    blocks.push(node.index()..node.index() + 1);
+    for child in node.children() {
+        query_blocks(child, blocks);
+    }
+}
+
+impl<'p, P> BlockSearch<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    fn new(tree: &'p KDTree<P>, range: Range<usize>, neighbour_count: usize) -> Self {
+        let queries = &tree.points()[range.clone()];
+        let dimension = tree.root().bounds().min.len();
+
+        let coords: Vec<f32> = (0..dimension)
+            .flat_map(|axis| queries.iter().map(move |query| query.get_coordinate_at(axis)))
+            .collect();
+
+        let mut min = vec![];
+        let mut max = vec![];
+        for axis_coords in coords.chunks(queries.len()) {
+            min.push(axis_coords.iter().copied().fold(f32::INFINITY, f32::min));
+            max.push(axis_coords.iter().copied().fold(f32::NEG_INFINITY, f32::max));
+        }
+        let middle = (0..dimension).map(|axis| (min[axis] + max[axis]) / 2.0).collect();
+
+        BlockSearch {
+            queries,
+            first_index: range.start,
+            coords,
+            dimension,
+            min,
+            max,
+            middle,
+            candidates: vec![(f32::INFINITY, 0); queries.len() * neighbour_count],
+            lengths: vec![0; queries.len()],
+            worst: vec![f32::INFINITY; queries.len()],
+            bound: f32::INFINITY,
+            distances: vec![0.0; queries.len()],
+            neighbour_count,
+        }
+    }
+
+    fn run(mut self, root: Node<'_, P>) -> Self {
+        self.search(root);
+        self
+    }
+
+    fn candidates_of(&self, query: usize) -> &[(f32, usize)] {
+        let start = query * self.neighbour_count;
+        &self.candidates[start..start + self.lengths[query]]
+    }
+
+    fn search(&mut self, node: Node<'_, P>) {
+        let query_bounds = Bounds { min: &self.min, max: &self.max };
+        if node.bounds().distance_to_bounds(&query_bounds) >= self.bound {
+            return;
+        }
+
+        let axis = node.axis();
+        let split = node.point().get_coordinate_at(axis);
+        let (near, far) = if self.middle[axis] > split { (node.right(), node.left()) } else { (node.left(), node.right()) };
+
+        if let Some(near) = near {
+            self.search(near);
+        }
+        self.visit_point(node);
+        if let Some(far) = far {
+            self.search(far);
+        }
+    }
+
+    fn visit_point(&mut self, node: Node<'_, P>) {
+        let index = node.index();
+        let point = node.point();
+        let count = self.queries.len();
+
+        self.distances.fill(0.0);
+        for axis in 0..self.dimension {
+            let value = point.get_coordinate_at(axis);
+            let axis_coords = &self.coords[axis * count..(axis + 1) * count];
+            for (distance, coord) in self.distances.iter_mut().zip(axis_coords) {
+                let delta = coord - value;
+                *distance += delta * delta;
+            }
+        }
+
+        let mut improved = false;
+        for query in 0..count {
+            let distance = self.distances[query];
+            if distance < self.worst[query] && self.first_index + query != index {
+                self.insert_candidate(query, (distance, index));
+                improved = true;
+            }
+        }
+        if improved {
+            self.bound = self.worst.iter().copied().fold(0.0, f32::max).sqrt();
+        }
+    }
+
+    fn insert_candidate(&mut self, query: usize, candidate: (f32, usize)) {
+        let neighbour_count = self.neighbour_count;
+        let list = &mut self.candidates[query * neighbour_count..(query + 1) * neighbour_count];
+        let length = (self.lengths[query] + 1).min(neighbour_count);
+        let position = list[..length - 1].partition_point(|(distance, _)| *distance <= candidate.0);
+
+        list.copy_within(position..length - 1, position + 1);
+        list[position] = candidate;
+        self.lengths[query] = length;
+        if length == neighbour_count {
+            self.worst[query] = list[neighbour_count - 1].0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Ikd::IKDTree;
+    use crate::tree::test_helpers::sorted;
+
+    #[test]
+    fn test_knn_graph_excludes_itself() {
+        let mut points = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(3.0, 0.0, 0.0),
+            Point3D::new(3.0, 0.0, 0.0),
+            Point3D::new(7.0, 0.0, 0.0),
+        ];
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let graph = KnnGraph::from_tree(&root, 2);
+
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.points, points);
+        assert_eq!(graph.offsets.len(), 6);
+        assert_eq!(graph.neighbours.len(), 10);
+
+        for index in 0..graph.len() {
+            let (neighbours, distances) = graph.neighbours_of(index);
+            assert!(!neighbours.contains(&index));
+            assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+        }
+
+        // The duplicated point is the nearest other point of its twin.
+        let duplicate = graph.points.iter().position(|p| p.x == 3.0).unwrap();
+        let (neighbours, distances) = graph.neighbours_of(duplicate);
+        assert_eq!(graph.points[neighbours[0]].x, 3.0);
+        assert_eq!(distances[0], 0.0);
+
+        // A neighbour count beyond the other points links every point to all of them.
+        let complete = KnnGraph::from_tree(&root, usize::MAX);
+        assert_eq!(complete.neighbours.len(), 20);
+        assert!((0..complete.len()).all(|index| complete.neighbours_of(index).0.len() == 4));
+    }
+
+    #[test]
+    fn test_knn_graph_matches_brute_force() {
+        let mut points = Point3D::generate_points(500, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let graph = KnnGraph::from_tree(&root, 4);
+
+        for index in 0..graph.len() {
+            let expected = sorted(graph.points.iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .map(|(_, other)| graph.points[index].distance_to(other))
+                .collect());
+
+            let (_, distances) = graph.neighbours_of(index);
+            assert_eq!(distances, &expected[..4]);
+        }
+
+        let empty = KnnGraph::from_tree(&root, 0);
+        assert_eq!(empty.offsets, vec![0; 501]);
+        assert!(empty.neighbours.is_empty());
+    }
+}
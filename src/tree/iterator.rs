@@ -0,0 +1,282 @@
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt::Debug;
+use crate::points::point::{Point, Point3D};
+use super::Ikd::IIterator;
+use super::kdtree::{KDTree, Node};
+use super::queue::QueueItem;
+
+enum Entry<'p, P> {
+    // Subtree keyed by the distance to its bounding box.
+    Node(Node<'p, P>),
+    // Single point keyed by its exact distance.
+    Point(&'p P),
+}
+
+/*
+Concrete Iterator
+=================
+Yields the points of a KDTree in increasing distance from the query point.
+Nodes and points share one priority queue; a node never sorts before a point
+that is closer than its bounding box, so a point popped from the queue is
+always the nearest one not returned yet.
+*/
+pub struct NearestIterator<'p, P> {
+    query_point: P,
+    query_coord: Vec<f32>,
+    queue: BinaryHeap<QueueItem<Entry<'p, P>>>,
+}
+
+impl<'p, P> NearestIterator<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    pub fn new(tree: &'p KDTree<P>, query_point: &P) -> Self {
+        let query_coord = KDTree::coordinate(query_point);
+
+        let mut queue = BinaryHeap::new();
+        let root = tree.root();
+        queue.push(QueueItem::new(root.bounds().min_distance(&query_coord), Entry::Node(root)));
+
+        NearestIterator { query_point: *query_point, query_coord, queue }
+    }
+}
+
+impl<'p, P> IIterator for NearestIterator<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    type Item = (f32, &'p P);
+
+    fn get_next(&mut self) -> Option<Self::Item> {
+        while let Some(QueueItem { distance, item }) = self.queue.pop() {
+            match item {
+                Entry::Point(point) => return Some((distance, point)),
+
+                Entry::Node(node) => {
+                    let point = node.point();
+                    self.queue.push(QueueItem::new(self.query_point.distance_to(point), Entry::Point(point)));
+
+                    for child in node.children() {
+                        let bound = child.bounds().min_distance(&self.query_coord);
+                        self.queue.push(QueueItem::new(bound, Entry::Node(child)));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn has_more(&self) -> bool {
+        // Every queued node holds at least one point.
+        !self.queue.is_empty()
+    }
+}
+
+impl<'p, P> Iterator for NearestIterator<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    type Item = (f32, &'p P);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.get_next()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraversalOrder {
+    PreOrder,
+    InOrder,
+    LevelOrder,
+    Leaves,
+}
+
+/* Node yielded by TraversalIterator. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeVisit<'p, P> {
+    pub point: &'p P,
+    pub depth: usize,
+    // Axis the node splits its subtree on.
+    pub axis: usize,
+}
+
+/*
+Concrete Iterator
+=================
+Walks the nodes of a KDTree in the given order. pending is used as a stack
+for the depth-first orders and as a queue for LevelOrder.
+*/
+pub struct TraversalIterator<'p, P> {
+    order: TraversalOrder,
+    pending: VecDeque<Node<'p, P>>,
+    // Next subtree whose left spine still has to be stacked, InOrder only.
+    current: Option<Node<'p, P>>,
+}
+
+impl<'p, P> TraversalIterator<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    pub fn new(tree: &'p KDTree<P>, order: TraversalOrder) -> Self {
+        let mut pending = VecDeque::new();
+        let mut current = None;
+
+        match order {
+            TraversalOrder::InOrder => current = Some(tree.root()),
+            _ => pending.push_back(tree.root()),
+        }
+
+        TraversalIterator { order, pending, current }
+    }
+
+    fn next_node(&mut self) -> Option<Node<'p, P>> {
+        match self.order {
+            TraversalOrder::PreOrder | TraversalOrder::Leaves => {
+                let node = self.pending.pop_back()?;
+                // Right goes first so that left is popped first.
+                self.pending.extend([node.right(), node.left()].into_iter().flatten());
+                Some(node)
+            }
+
+            TraversalOrder::InOrder => {
+                while let Some(node) = self.current {
+                    self.pending.push_back(node);
+                    self.current = node.left();
+                }
+
+                let node = self.pending.pop_back()?;
+                self.current = node.right();
+                Some(node)
+            }
+
+            TraversalOrder::LevelOrder => {
+                let node = self.pending.pop_front()?;
+                self.pending.extend(node.children());
+                Some(node)
+            }
+        }
+    }
+}
+
+impl<'p, P> IIterator for TraversalIterator<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    type Item = NodeVisit<'p, P>;
+
+    fn get_next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.next_node() {
+            if self.order == TraversalOrder::Leaves && !node.is_leaf() {
+                continue;
+            }
+
+            return Some(NodeVisit {
+                point: node.point(),
+                depth: node.depth(),
+                axis: node.axis(),
+            });
+        }
+
+        None
+    }
+
+    fn has_more(&self) -> bool {
+        // Every subtree holds at least one leaf, so this also holds for Leaves.
+        !self.pending.is_empty() || self.current.is_some()
+    }
+}
+
+impl<'p, P> Iterator for TraversalIterator<'p, P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    type Item = NodeVisit<'p, P>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.get_next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::tree::Ikd::IKDTree;
+    use crate::tree::test_helpers::brute_force_distances;
+
+    #[test]
+    fn test_yields_every_point_in_order() {
+        let mut points = Point3D::generate_points(1_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let query_point = Point3D::new(2.0, 12.0, 5.0);
+        let distances: Vec<f32> = root.nearest_iter(&query_point).map(|(distance, _)| distance).collect();
+
+        assert_eq!(distances, brute_force_distances(&all_points, &query_point));
+    }
+
+    #[test]
+    fn test_get_next_and_has_more() {
+        let mut points = vec![
+            Point3D::new(1.0, 1.0, 1.0),
+            Point3D::new(2.0, 2.0, 2.0),
+            Point3D::new(3.0, 3.0, 3.0),
+        ];
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let mut iterator = root.nearest_iter(&Point3D::new(0.0, 0.0, 0.0));
+        assert_eq!(iterator.get_next().unwrap().1, &Point3D::new(1.0, 1.0, 1.0));
+        assert_eq!(iterator.get_next().unwrap().1, &Point3D::new(2.0, 2.0, 2.0));
+        assert!(iterator.has_more());
+        assert_eq!(iterator.get_next().unwrap().1, &Point3D::new(3.0, 3.0, 3.0));
+        assert!(!iterator.has_more());
+        assert!(iterator.get_next().is_none());
+
+        // Callers stop on their own condition instead of a fixed limit.
+        let within: Vec<_> = root.nearest_iter(&Point3D::new(0.0, 0.0, 0.0))
+            .take_while(|(distance, _)| *distance < 3.5)
+            .collect();
+        assert_eq!(within.len(), 2);
+    }
+
+    fn traversal_tree() -> Arc<KDTree<Point3D>> {
+        let mut points = vec![
+            Point3D::new(1.0, 2.0, 3.0),
+            Point3D::new(4.0, 5.0, 6.0),
+            Point3D::new(7.0, 8.0, 9.0),
+            Point3D::new(2.0, 3.0, 4.0),
+            Point3D::new(5.0, 6.0, 7.0),
+            Point3D::new(8.0, 9.0, 10.0),
+        ];
+        KDTree::create_kd_tree(&mut points, 0, 3).unwrap()
+    }
+
+    fn x_values(visits: Vec<NodeVisit<Point3D>>) -> Vec<f32> {
+        visits.iter().map(|visit| visit.point.x).collect()
+    }
+
+    #[test]
+    fn test_depth_first_orders() {
+        let root = traversal_tree();
+
+        let pre_order: Vec<_> = root.traverse(TraversalOrder::PreOrder).collect();
+        assert_eq!(x_values(pre_order.clone()), vec![5.0, 2.0, 1.0, 4.0, 8.0, 7.0]);
+        assert_eq!(pre_order[1].depth, 1);
+        assert_eq!(pre_order[1].axis, 1);
+
+        let in_order: Vec<_> = root.traverse(TraversalOrder::InOrder).collect();
+        assert_eq!(x_values(in_order), vec![1.0, 2.0, 4.0, 5.0, 7.0, 8.0]);
+
+        let leaves: Vec<_> = root.traverse(TraversalOrder::Leaves).collect();
+        assert_eq!(x_values(leaves.clone()), vec![1.0, 4.0, 7.0]);
+        assert!(leaves.iter().all(|visit| visit.depth == 2 && visit.axis == 2));
+    }
+
+    #[test]
+    fn test_level_order() {
+        let root = traversal_tree();
+
+        let mut iterator = root.traverse(TraversalOrder::LevelOrder);
+        assert_eq!(iterator.get_next().unwrap().depth, 0);
+        assert!(iterator.has_more());
+
+        let depths: Vec<usize> = iterator.map(|visit| visit.depth).collect();
+        assert_eq!(depths, vec![1, 1, 2, 2, 2]);
+    }
+}
@@ -1,17 +1,146 @@
 use std::cmp::{Ordering, PartialEq};
+use std::collections::BinaryHeap;
 use std::fmt::Debug;
-use std::rc::Rc;
+use std::ops::Range;
+use std::sync::Arc;
+use rayon::prelude::*;
+use crate::points::line::Line;
 use crate::points::point::{Point, Point3D};
+use crate::tree::bounds::Bounds;
 use crate::tree::error_handler::ComparisonError;
-use super::Ikd::{IIterator, IKDTree, NodeDirection};
-
+use crate::tree::iterator::{NearestIterator, TraversalIterator, TraversalOrder};
+use crate::tree::queue::QueueItem;
+use super::Ikd::{IKDTree, NodeDirection};
+
+/*
+Implicit balanced tree, every node lives in two flat arrays instead of its own allocation.
+The node of the range [start, end) is the point in the middle of it, its left subtree is
+[start, middle) and its right subtree is [middle + 1, end).
+*/
 #[derive(Debug)]
 pub struct KDTree<P>
 {
-    pub point: Option<Rc<P>>,
+    // Points in tree order.
+    points: Vec<P>,
+    // Bounding box of the subtree of each node, min then max coordinates, indexed like points.
+    bounds: Vec<f32>,
+    // Amount of coordinates of a point.
+    dimension: usize,
+    // Amount of axes the tree alternates between.
+    k: usize,
+    // Depth of the root node.
+    depth: usize,
+}
+
+/*
+Borrowed handle on a subtree of a KDTree, it is as cheap to copy as an index.
+*/
+pub struct Node<'t, P> {
+    tree: &'t KDTree<P>,
+    start: usize,
+    end: usize,
     depth: usize,
-    pub left: Option<Rc<KDTree<P>>>,
-    pub right: Option<Rc<KDTree<P>>>,
+}
+
+impl<'t, P> Clone for Node<'t, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, P> Copy for Node<'t, P> {}
+
+impl<'t, P> Node<'t, P> {
+    fn new(tree: &'t KDTree<P>, start: usize, end: usize, depth: usize) -> Self {
+        Node { tree, start, end, depth }
+    }
+
+    /* Position of the point of this node in points(). */
+    pub(crate) fn index(&self) -> usize {
+        self.start + (self.end - self.start) / 2
+    }
+
+    /* Positions in points() of every point of this subtree, they are contiguous. */
+    pub(crate) fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn point(&self) -> &'t P {
+        &self.tree.points[self.index()]
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /* Axis the node splits its subtree on. */
+    pub fn axis(&self) -> usize {
+        self.depth % self.tree.k
+    }
+
+    /* Amount of points in this subtree, the current node included. */
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.size() == 1
+    }
+
+    pub fn left(&self) -> Option<Node<'t, P>> {
+        let index = self.index();
+        (self.start < index).then(|| Node::new(self.tree, self.start, index, self.depth + 1))
+    }
+
+    pub fn right(&self) -> Option<Node<'t, P>> {
+        let index = self.index();
+        (index + 1 < self.end).then(|| Node::new(self.tree, index + 1, self.end, self.depth + 1))
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = Node<'t, P>> {
+        [self.left(), self.right()].into_iter().flatten()
+    }
+
+    /* Bounding box of every point in this subtree. */
+    pub(crate) fn bounds(&self) -> Bounds<'t> {
+        let dimension = self.tree.dimension;
+        let offset = self.index() * 2 * dimension;
+        Bounds {
+            min: &self.tree.bounds[offset..offset + dimension],
+            max: &self.tree.bounds[offset + dimension..offset + 2 * dimension],
+        }
+    }
+}
+
+impl<P> KDTree<P> {
+    pub fn root(&self) -> Node<'_, P> {
+        Node::new(self, 0, self.points.len(), self.depth)
+    }
+
+    /* Amount of axes the tree alternates between, the k it was built with. */
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /*
+    Every point of the tree, in tree order, which is also the order create_kd_tree left its input in.
+    Results holding one entry per stored point, e.g. KnnGraph, dbscan, estimate_normals and residuals,
+    are indexed like this slice.
+    */
+    pub fn points(&self) -> &[P] {
+        &self.points
+    }
+
+    /*
+    Position in points() of a point stored in this tree, e.g. one returned by a query.
+    Panics when point is not stored in this tree.
+    */
+    pub(crate) fn index_of(&self, point: &P) -> usize {
+        let point = point as *const P;
+        assert!(self.points.as_ptr_range().contains(&point), "point is not stored in this tree");
+
+        (point as usize - self.points.as_ptr() as usize) / std::mem::size_of::<P>()
+    }
 }
 
 /**
@@ -22,127 +151,49 @@ impl<P> IKDTree<P> for KDTree<P>
 {
     type Output = KDTree<P>;
 
-    fn create_kd_tree(points: &mut Vec<P>, depth: usize, k: usize) -> Result<Rc<KDTree<P>>, String> {
-        if points.len() == 0 {
-            return Err(String::from("KDTreeBuildError: point len is zero."));
-        }
+    fn create_kd_tree(points: &mut Vec<P>, depth: usize, k: usize) -> Result<Arc<KDTree<P>>, String> {
+        Self::check_build(points, k)?;
 
-        // In order to get almost perfect balance tree, we have to sort it first.
-        points.sort_by(|a, b| Self::sorting_point(a, b, 0).unwrap());
+        // Points are arranged in place, the tree keeps its own copy of them.
+        Self::build_kd_tree(points, k, depth);
 
-        // Following code will init an KDTree object with zero value.
-        if let Some(kd_tree) = Self::build_kd_tree(
-            Self::init(),
-            points,
-            3,
-            0
-        ){
-            return Ok(kd_tree)
-        }else {
-            Err(String::from("KDTreeBuildError: Error occurs while building KDTree"))
-        }
+        Ok(Arc::new(Self::from_arranged(points.clone(), k, depth)))
     }
 
     fn build_kd_tree
     (
-        mut init_kd_tree: Self::Output,
-        sorted_points: &mut Vec<P>,
+        points: &mut [P],
         k: usize,
         depth: usize
-    ) -> Option<Rc<Self::Output>>
+    )
     {
-        let axis = depth % k;
-
-        // In order to get almost perfect balance tree, we have to sort it first.
-        sorted_points.sort_by(|a, b| Self::sorting_point(a, b, axis).unwrap());
-
-        // find the median
-        let median = sorted_points.len() / 2;
-
-        // Update current node.
-        init_kd_tree.point = Some(Rc::new(sorted_points[median]));
-        init_kd_tree.depth = depth;
-
         // Median 0 means there is no points left to operate.
-        if median != 0 {
-
-            // Only left node to create
-            if median == 1 && sorted_points.len() == 2 {
-                // Create Left nodes
-                let left_point = Self::operation_point_list(
-                    &sorted_points,
-                    median,
-                    NodeDirection::LEFT
-                );
-
-                match Self::build_kd_tree(
-                    Self::init(),
-                    &mut left_point.to_vec(),
-                    3,
-                    depth + 1
-                )
-                {
-                    Some(left_child_node) => {
-                        init_kd_tree.left = Some(Rc::clone(&left_child_node));
-                    },
+        if points.is_empty() {
+            return;
+        }
 
-                    _ => ()
-                }
-            } else {
-                // Create Left nodes
-                let left_point = Self::operation_point_list(
-                    &sorted_points,
-                    median,
-                    NodeDirection::LEFT
-                );
-
-                match Self::build_kd_tree(
-                    Self::init(),
-                    &mut left_point.to_vec(),
-                    3,
-                    depth + 1
-                )
-                {
-                    Some(left_child_node) => {
-                        init_kd_tree.left = Some(Rc::clone(&left_child_node));
-                    },
+        let axis = depth % k;
 
-                    _ => ()
-                }
+        // find the median
+        let median = points.len() / 2;
 
-                // Create Right nodes
-                let right_point = Self::operation_point_list(
-                    &sorted_points,
-                    median,
-                    NodeDirection::RIGHT
-                );
-
-                match Self::build_kd_tree(
-                    Self::init(),
-                    &mut right_point.to_vec(),
-                    3,
-                    depth + 1
-                )
-                {
-                    Some(right_child_node) => {
-                        init_kd_tree.right = Some(Rc::clone(&right_child_node));
-                    },
-
-                    _ => ()
-                }
-            }
-        }
-                Some(Rc::new(init_kd_tree))
+        /*
+        Selecting the median moves every point not greater than it to its left and every
+        point not smaller than it to its right in linear time, both halves are then
+        arranged in place. Nothing is sorted and nothing is copied.
+        */
+        points.select_nth_unstable_by(median, |a, b| Self::sorting_point(a, b, axis).unwrap());
 
+        let (left_points, right_points) = points.split_at_mut(median);
+        Self::build_kd_tree(left_points, k, depth + 1);
+        Self::build_kd_tree(&mut right_points[1..], k, depth + 1);
     }
 
-    fn init() -> Self::Output {
-        KDTree {
-            point: None,
-            depth: 0,
-            left: None,
-            right: None,
-        }
+    fn init(mut points: Vec<P>, k: usize, depth: usize) -> Result<Self::Output, String> {
+        Self::check_build(&points, k)?;
+        Self::build_kd_tree(&mut points, k, depth);
+
+        Ok(Self::from_arranged(points, k, depth))
     }
 
     fn sorting_point(
@@ -151,18 +202,10 @@ impl<P> IKDTree<P> for KDTree<P>
         axis: usize
     ) -> Result<Ordering, ComparisonError>
     {
-        let point_a_cord = point_a.get_coordinate();
-        let point_b_cord = point_b.get_coordinate();
-
+        // Called for every comparison of the build, so coordinates are read one at a time.
         match axis {
-            // Compare x dimension
-            0 => Ok(point_a_cord[0].partial_cmp(&point_b_cord[0]).unwrap()),
-
-            // Compare y dimension
-            1 => Ok(point_a_cord[1].partial_cmp(&point_b_cord[1]).unwrap()),
-
-            // Compare z dimension
-            2 => Ok(point_a_cord[2].partial_cmp(&point_b_cord[2]).unwrap()),
+            // Compare x, y or z dimension
+            0..=2 => Ok(point_a.get_coordinate_at(axis).partial_cmp(&point_b.get_coordinate_at(axis)).unwrap()),
 
             _ => Err(
                 ComparisonError::InvalidOrdering(
@@ -179,129 +222,572 @@ impl<P> IKDTree<P> for KDTree<P>
         Ok(n_point_a.0.partial_cmp(&n_point_b.0).unwrap())
     }
 
-    fn operation_point_list(
-        points: &Vec<P>,
-        median: usize,
-        node_direction: NodeDirection
-    ) -> &[P]
+    fn find_closest(&self, query_point: &P, _k: usize, point_limit: usize) -> Option<Vec<(f32, &P)>> {
+        // Every node splits on the axis of the k the tree was built with, whatever k is passed.
+        self.find_closest_approx(query_point, point_limit, 0.0)
+    }
+
+    fn find_closest_excluding(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        excluded: &P
+    ) -> Option<Vec<(f32, &P)>>
+    {
+        self.find_closest_where(query_point, point_limit, |point| !std::ptr::eq(point, excluded))
+    }
+
+    fn find_closest_batch(
+        &self,
+        queries: &[P],
+        point_limit: usize
+    ) -> Vec<Option<Vec<(f32, &P)>>>
+        where P: Send + Sync
     {
-        match node_direction {
-            NodeDirection::LEFT => {
-                &points[..median]
+        // Queries only read the tree, so a single tree is shared by every worker thread.
+        queries.par_iter()
+            .map(|query_point| self.find_closest(query_point, self.k, point_limit))
+            .collect()
+    }
+
+    fn find_closest_approx(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        epsilon: f32
+    ) -> Option<Vec<(f32, &P)>>
+    {
+        Self::closest_search(self.root(), query_point, point_limit, epsilon, &|_| true)
+    }
+
+    fn find_closest_where<F>(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        filter: F
+    ) -> Option<Vec<(f32, &P)>>
+        where F: Fn(&P) -> bool
+    {
+        Self::closest_search(self.root(), query_point, point_limit, 0.0, &filter)
+    }
+
+    fn direction(query_point: &P, node_point: &P, axis: usize) -> NodeDirection{
+        // If Query point is greater than current point then go right.
+        if query_point.get_coordinate_at(axis) - node_point.get_coordinate_at(axis) > 0f32 {
+            NodeDirection::RIGHT
+        }
+
+        // If Query point is greater than current point then go left.
+        else {
+            NodeDirection::LEFT
+        }
+    }
+
+    fn within_radius(&self, query_point: &P, radius: f32) -> Vec<(f32, &P)> {
+        let mut found_points = vec![];
+        Self::radius_search(self.root(), query_point, radius, &mut found_points);
+
+        found_points.sort_by(|a, b| Self::sorting_nearest(a, b).unwrap());
+        found_points
+    }
+
+    fn count_within_radius(&self, query_point: &P, radius: f32) -> usize {
+        Self::radius_count(self.root(), query_point, &Self::coordinate(query_point), radius)
+    }
+
+    fn count_in_box(&self, min_point: &P, max_point: &P) -> usize {
+        Self::box_count(self.root(), &Self::coordinate(min_point), &Self::coordinate(max_point))
+    }
+
+    fn find_closest_to_line(&self, line: &Line, point_limit: usize) -> Option<Vec<(f32, &P)>> {
+        if point_limit == 0 {
+            return None;
+        }
+
+        let mut best_points = Vec::with_capacity(point_limit + 1);
+        Self::line_search(self.root(), line, &mut best_points, point_limit);
+
+        if !best_points.is_empty() {
+            return Some(best_points);
+        }
+
+        None
+    }
+
+    fn find_farthest(&self, query_point: &P, point_limit: usize) -> Option<Vec<(f32, &P)>> {
+        if point_limit == 0 {
+            return None;
+        }
+
+        let mut farthest_points = Vec::with_capacity(point_limit + 1);
+        Self::farthest_search(self.root(), query_point, &Self::coordinate(query_point), &mut farthest_points, point_limit);
+
+        if !farthest_points.is_empty() {
+            return Some(farthest_points);
+        }
+
+        None
+    }
+
+    fn find_closest_bbf(
+        &self,
+        query_point: &P,
+        point_limit: usize,
+        max_visits: usize
+    ) -> Option<Vec<(f32, &P)>>
+    {
+        if point_limit == 0 {
+            return None;
+        }
+
+        let mut best_points = Vec::with_capacity(point_limit + 1);
+        let mut visits = 0;
+
+        // Each entry is a subtree together with a lower bound of its distance to the query point.
+        let mut queue = BinaryHeap::new();
+        queue.push(QueueItem::new(0.0, self.root()));
+
+        'search: while let Some(QueueItem { distance: bound, item: subtree }) = queue.pop() {
+            // Every remaining subtree is farther than the worst candidate.
+            if bound >= Self::worst_distance(&best_points, point_limit) {
+                break;
             }
-            NodeDirection::RIGHT => {
-                &points[median+1..]
+
+            // Walk down to a leaf, leaving the other sides in the queue.
+            let mut next_node = Some(subtree);
+            while let Some(node) = next_node {
+                if visits == max_visits {
+                    break 'search;
+                }
+                visits += 1;
+
+                let axis = node.axis();
+                let point = node.point();
+                Self::insert_candidate(&mut best_points, (query_point.distance_to(point), point), point_limit);
+
+                let (near_node, far_node) = match Self::direction(query_point, point, axis) {
+                    NodeDirection::LEFT => (node.left(), node.right()),
+                    NodeDirection::RIGHT => (node.right(), node.left()),
+                };
+
+                if let Some(far_node) = far_node {
+                    let distance_to_plane = Self::axis_distance(query_point, point, axis).abs();
+                    queue.push(QueueItem::new(distance_to_plane.max(bound), far_node));
+                }
+
+                next_node = near_node;
             }
         }
+
+        if !best_points.is_empty() {
+            return Some(best_points);
+        }
+
+        None
     }
 
-    fn find_closest(&self, query_point: &P, k: usize, point_limit: usize) -> Option<Vec<(f32, &P)>> {
-        let mut best_points_list = vec![];
-        best_points_list = Self::nearest_neighbour(
-            &self,
-            f32::MAX,
-            query_point,
-            best_points_list,
-            k
-        );
+    fn within_box(&self, min_point: &P, max_point: &P) -> Vec<&P> {
+        let mut found_points = vec![];
+        self.visit_box(min_point, max_point, |point| found_points.push(point));
+        found_points
+    }
+
+    fn visit_box<'p, F>(&'p self, min_point: &P, max_point: &P, mut visitor: F)
+        where F: FnMut(&'p P), P: 'p
+    {
+        let min_coord = Self::coordinate(min_point);
+        let max_coord = Self::coordinate(max_point);
+
+        Self::box_search(self.root(), &min_coord, &max_coord, &mut visitor);
+    }
+
+    fn nearest_iter(&self, query_point: &P) -> NearestIterator<'_, P> {
+        NearestIterator::new(self, query_point)
+    }
+
+    fn traverse(&self, order: TraversalOrder) -> TraversalIterator<'_, P> {
+        TraversalIterator::new(self, order)
+    }
+}
 
-        best_points_list.sort_by(|a, b| Self::sorting_nearest(a,b).unwrap());
+impl<P> KDTree<P>
+    where P: Point<Point3D> + Copy + PartialEq + Debug
+{
+    /*
+    Stores points already arranged by build_kd_tree and checked by check_build,
+    the bounding boxes are computed here.
+    */
+    fn from_arranged(points: Vec<P>, k: usize, depth: usize) -> Self {
+        let dimension = Self::dimension(&points[0]);
+
+        let mut kd_tree = KDTree {
+            bounds: vec![0.0; points.len() * 2 * dimension],
+            points,
+            dimension,
+            k,
+            depth,
+        };
+        kd_tree.fill_bounds(0, kd_tree.points.len());
+        kd_tree
+    }
 
-        if best_points_list.len() >= point_limit {
-            return Some(best_points_list[..point_limit].to_vec());
+    /*
+    Bounding box of the node of [start, end) grown over the boxes of its children,
+    children are filled first.
+    */
+    fn fill_bounds(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
         }
 
-        else if best_points_list.len() > 0 {
-           return Some(best_points_list);
+        let index = start + (end - start) / 2;
+        self.fill_bounds(start, index);
+        self.fill_bounds(index + 1, end);
+
+        let dimension = self.dimension;
+        let offset = index * 2 * dimension;
+        for axis in 0..dimension {
+            let value = self.points[index].get_coordinate_at(axis);
+            self.bounds[offset + axis] = value;
+            self.bounds[offset + dimension + axis] = value;
+        }
+
+        let children = [(start < index).then(|| start + (index - start) / 2), (index + 1 < end).then(|| index + 1 + (end - index - 1) / 2)];
+        for child in children.into_iter().flatten() {
+            let child_offset = child * 2 * dimension;
+            for axis in 0..dimension {
+                self.bounds[offset + axis] = self.bounds[offset + axis].min(self.bounds[child_offset + axis]);
+                self.bounds[offset + dimension + axis] = self.bounds[offset + dimension + axis].max(self.bounds[child_offset + dimension + axis]);
+            }
+        }
+    }
+
+    /*
+    Signed distance from the splitting plane of node_point to the query point along axis.
+    Called at every visited node, so only the coordinates on axis are read.
+    */
+    fn axis_distance(query_point: &P, node_point: &P, axis: usize) -> f32 {
+        query_point.get_coordinate_at(axis) - node_point.get_coordinate_at(axis)
+    }
+
+    /*
+    Entry point shared by the k nearest neighbour queries.
+    */
+    fn closest_search<'p, F>(
+        node: Node<'p, P>,
+        query_point: &P,
+        point_limit: usize,
+        epsilon: f32,
+        filter: &F
+    ) -> Option<Vec<(f32, &'p P)>>
+        where F: Fn(&P) -> bool
+    {
+        if point_limit == 0 {
+            return None;
+        }
+
+        // Candidates are kept sorted by distance, so no sorting is needed afterwards.
+        let best_points_list = Self::nearest_neighbour(
+            node,
+            query_point,
+            Vec::with_capacity(point_limit + 1),
+            point_limit,
+            epsilon.max(0.0),
+            filter
+        );
+
+        if !best_points_list.is_empty() {
+            return Some(best_points_list);
         }
 
         None
     }
 
+    /*
+    Recursive k nearest neighbour search.
+    best_points holds the candidates found so far, sorted by distance and never longer than point_limit.
+    Subtrees that cannot improve the worst candidate by more than (1 + epsilon) are pruned,
+    points rejected by filter are never added to best_points.
+    */
     fn nearest_neighbour
-    <'p>
+    <'p, F>
     (
-        node: &'p Self::Output,
-        mut max_distance_sq: f32,
+        node: Node<'p, P>,
         query_point: &P,
         mut best_points: Vec<(f32, &'p P)>,
-        k: usize
+        point_limit: usize,
+        epsilon: f32,
+        filter: &F
     ) -> Vec<(f32, &'p P)>
+        where F: Fn(&P) -> bool
     {
-        let axis = node.depth % k;
-        let point = node.point.as_ref().unwrap().as_ref();
-
-        let left_node = node.left.as_ref();
-        let right_node = node.right.as_ref();
+        let axis = node.axis();
+        let point = node.point();
 
         // Calculate the distance between current node and query point.
-        let mut current_node_distance = query_point.distance_to(point);
-
-        // Only current_dist is shorter than root dist.
-        if current_node_distance < max_distance_sq {
-
-            max_distance_sq = current_node_distance;
-            best_points.push((max_distance_sq, point));
-
-            // if it's not leaf then decided to choose left or right.
-            let mut direction = Self::direction(query_point, point, axis);
-
-            // Make sure Direction have node.
-            if (direction == NodeDirection::LEFT && !left_node.is_none()) ||
-                (direction == NodeDirection::RIGHT && !right_node.is_none())
-            {
-                let mut distance_to_op_side = f32::MAX;
-
-                // Follow to correct child node.
-                if direction == NodeDirection::RIGHT {
-                    best_points = Self::nearest_neighbour(right_node.unwrap(), max_distance_sq, query_point, best_points, k);
-
-                    /*
-                     * IN Case: we missed.
-                     * We may need to check the other side of the tree. If the other side is closer than the radius
-                     */
-                    if !left_node.is_none() {
-                        distance_to_op_side = query_point.distance_to(left_node.unwrap().point.as_ref().unwrap().as_ref());
-                        if distance_to_op_side < max_distance_sq { direction = NodeDirection::LEFT };
-                    }
-                }
+        let current_node_distance = query_point.distance_to(point);
 
-                else if direction == NodeDirection::LEFT {
-                    best_points = Self::nearest_neighbour(left_node.unwrap(), max_distance_sq, query_point, best_points, k);
-                    if !right_node.is_none() {
-                        distance_to_op_side = query_point.distance_to(right_node.unwrap().point.as_ref().unwrap().as_ref());
-                        if distance_to_op_side < max_distance_sq { direction = NodeDirection::RIGHT };
-                    }
-                }
+        if filter(point) {
+            Self::insert_candidate(&mut best_points, (current_node_distance, point), point_limit);
+        }
 
-                // Make sure we have to go to child node.
-                if distance_to_op_side < max_distance_sq {
-                    if direction == NodeDirection::LEFT {
-                        best_points =Self::nearest_neighbour(left_node.unwrap(), max_distance_sq, query_point, best_points, k);
-                    } else if direction == NodeDirection::RIGHT {
-                        best_points = Self::nearest_neighbour(right_node.unwrap(), max_distance_sq, query_point, best_points, k);
-                    }
-                }
+        // Visit the side of the splitting plane holding the query point first.
+        let (near_node, far_node) = match Self::direction(query_point, point, axis) {
+            NodeDirection::LEFT => (node.left(), node.right()),
+            NodeDirection::RIGHT => (node.right(), node.left()),
+        };
 
-                return best_points;
+        if let Some(near_node) = near_node {
+            best_points = Self::nearest_neighbour(near_node, query_point, best_points, point_limit, epsilon, filter);
+        }
+
+        /*
+         * The other side can only hold a closer point when the splitting plane itself
+         * is closer than the worst candidate, whatever the point stored in the child is.
+         * In approximate mode the plane has to be closer by a factor of (1 + epsilon).
+         */
+        if let Some(far_node) = far_node {
+            let distance_to_plane = Self::axis_distance(query_point, point, axis).abs();
+            if distance_to_plane * (1.0 + epsilon) < Self::worst_distance(&best_points, point_limit) {
+                best_points = Self::nearest_neighbour(far_node, query_point, best_points, point_limit, epsilon, filter);
             }
         }
 
         best_points
     }
 
-    fn direction(query_point: &P, node_point: &P, axis: usize) -> NodeDirection{
-        let node_coord = node_point.get_coordinate();
-        let q_coord = query_point.get_coordinate();
+    /* Owned copy of the coordinate of a point. */
+    pub(crate) fn coordinate(point: &P) -> Vec<f32> {
+        point.get_coordinate().into_iter().copied().collect()
+    }
 
-        // If Query point is greater than current point then go right.
-        if q_coord[axis] - node_coord[axis] > 0f32 {
-            NodeDirection::RIGHT
+    /*
+    A subtree is skipped when the line misses its bounding box grown by the
+    worst candidate distance.
+    */
+    fn line_search<'p>(
+        node: Node<'p, P>,
+        line: &Line,
+        best_points: &mut Vec<(f32, &'p P)>,
+        point_limit: usize
+    )
+    {
+        let worst = Self::worst_distance(best_points, point_limit);
+        if !line.hits_box(node.bounds().min, node.bounds().max, worst) {
+            return;
         }
 
-        // If Query point is greater than current point then go left.
-        else {
-            NodeDirection::LEFT
+        let point = node.point();
+        Self::insert_candidate(best_points, (line.distance_to_point(point), point), point_limit);
+
+        // Children closer to the line are visited first, it tightens the bound sooner.
+        let mut children = [node.left(), node.right()];
+        if let [Some(left_node), Some(right_node)] = children {
+            if Self::line_distance_to_center(line, right_node) < Self::line_distance_to_center(line, left_node) {
+                children.swap(0, 1);
+            }
+        }
+
+        for child in children.into_iter().flatten() {
+            Self::line_search(child, line, best_points, point_limit);
+        }
+    }
+
+    /* Distance from the line to the centre of the bounding box of node, computed axis by axis. */
+    fn line_distance_to_center(line: &Line, node: Node<'_, P>) -> f32 {
+        let bounds = node.bounds();
+        line.distance_by(|axis| (bounds.min[axis] + bounds.max[axis]) / 2.0)
+    }
+
+    /*
+    Subtrees entirely inside the sphere are counted from their size, subtrees
+    entirely outside of it are skipped, only the others are visited.
+    */
+    fn radius_count(node: Node<'_, P>, query_point: &P, query_coord: &[f32], radius: f32) -> usize {
+        if node.bounds().max_distance(query_coord) <= radius {
+            return node.size();
+        }
+        if node.bounds().min_distance(query_coord) > radius {
+            return 0;
+        }
+
+        let point = node.point();
+        let mut count = usize::from(query_point.distance_to(point) <= radius);
+
+        for child in node.children() {
+            count += Self::radius_count(child, query_point, query_coord, radius);
+        }
+        count
+    }
+
+    fn box_count(node: Node<'_, P>, min_coord: &[f32], max_coord: &[f32]) -> usize {
+        if node.bounds().is_inside_box(min_coord, max_coord) {
+            return node.size();
+        }
+        if !node.bounds().intersects_box(min_coord, max_coord) {
+            return 0;
+        }
+
+        let mut count = usize::from(Self::is_inside_box(node.point(), min_coord, max_coord));
+
+        for child in node.children() {
+            count += Self::box_count(child, min_coord, max_coord);
+        }
+        count
+    }
+
+    /*
+    farthest_points is sorted by decreasing distance, its last entry is the one to beat
+    once it holds point_limit points. A subtree whose farthest corner is not beyond
+    that distance cannot contribute.
+    */
+    fn farthest_search<'p>(
+        node: Node<'p, P>,
+        query_point: &P,
+        query_coord: &[f32],
+        farthest_points: &mut Vec<(f32, &'p P)>,
+        point_limit: usize
+    )
+    {
+        let is_full = farthest_points.len() >= point_limit;
+        if is_full && node.bounds().max_distance(query_coord) <= farthest_points[point_limit - 1].0 {
+            return;
+        }
+
+        let point = node.point();
+        let current_node_distance = query_point.distance_to(point);
+        if !is_full || current_node_distance > farthest_points[point_limit - 1].0 {
+            let position = farthest_points.partition_point(|(distance, _)| *distance >= current_node_distance);
+            farthest_points.insert(position, (current_node_distance, point));
+            farthest_points.truncate(point_limit);
+        }
+
+        // Visit the child reaching farther first, it tightens the bound sooner.
+        let mut children = [node.left(), node.right()];
+        if let [Some(left_node), Some(right_node)] = children {
+            if right_node.bounds().max_distance(query_coord) > left_node.bounds().max_distance(query_coord) {
+                children.swap(0, 1);
+            }
+        }
+
+        for child in children.into_iter().flatten() {
+            Self::farthest_search(child, query_point, query_coord, farthest_points, point_limit);
+        }
+    }
+
+    /*
+    Amount of axes the tree alternates between, taken from the point itself.
+    */
+    fn dimension(point: &P) -> usize {
+        point.get_coordinate().len()
+    }
+
+    /* Points and k a tree can be built from, checked once by create_kd_tree and by init. */
+    fn check_build(points: &[P], k: usize) -> Result<(), String> {
+        if points.is_empty() {
+            return Err(String::from("KDTreeBuildError: point len is zero."));
+        }
+
+        // Every level splits on axis depth % k, so k has to name existing axes.
+        let dimension = Self::dimension(&points[0]);
+        if k == 0 || k > dimension {
+            return Err(format!("KDTreeBuildError: k must be between 1 and {}, got {}.", dimension, k));
+        }
+        Ok(())
+    }
+
+    fn radius_search<'p>(
+        node: Node<'p, P>,
+        query_point: &P,
+        radius: f32,
+        found_points: &mut Vec<(f32, &'p P)>
+    )
+    {
+        let axis = node.axis();
+        let point = node.point();
+
+        let current_node_distance = query_point.distance_to(point);
+        if current_node_distance <= radius {
+            found_points.push((current_node_distance, point));
+        }
+
+        // A side of the splitting plane is skipped when the plane is out of the radius.
+        let distance_to_plane = Self::axis_distance(query_point, point, axis);
+
+        if let Some(left_node) = node.left() {
+            if distance_to_plane <= radius {
+                Self::radius_search(left_node, query_point, radius, found_points);
+            }
+        }
+
+        if let Some(right_node) = node.right() {
+            if -distance_to_plane <= radius {
+                Self::radius_search(right_node, query_point, radius, found_points);
+            }
+        }
+    }
+
+    fn box_search<'p, F>(
+        node: Node<'p, P>,
+        min_coord: &[f32],
+        max_coord: &[f32],
+        visitor: &mut F
+    )
+        where F: FnMut(&'p P), P: 'p
+    {
+        let axis = node.axis();
+        let point = node.point();
+
+        if Self::is_inside_box(point, min_coord, max_coord) {
+            visitor(point);
+        }
+
+        // Left side holds smaller or equal values on the axis, right side greater or equal ones.
+        let split_value = point.get_coordinate_at(axis);
+        if let Some(left_node) = node.left() {
+            if min_coord[axis] <= split_value {
+                Self::box_search(left_node, min_coord, max_coord, visitor);
+            }
+        }
+
+        if let Some(right_node) = node.right() {
+            if split_value <= max_coord[axis] {
+                Self::box_search(right_node, min_coord, max_coord, visitor);
+            }
+        }
+    }
+
+    /*
+    Whether point lies inside [min_coord, max_coord], bounds are inclusive.
+    Coordinates are read in place, nothing is allocated per visited node.
+    */
+    fn is_inside_box(point: &P, min_coord: &[f32], max_coord: &[f32]) -> bool {
+        (0..min_coord.len()).all(|axis| {
+            let value = point.get_coordinate_at(axis);
+            min_coord[axis] <= value && value <= max_coord[axis]
+        })
+    }
+
+    /*
+    Insert a candidate into the sorted candidate list when it beats the worst one,
+    the list never grows beyond point_limit.
+    */
+    fn insert_candidate<'p>(best_points: &mut Vec<(f32, &'p P)>, candidate: (f32, &'p P), point_limit: usize) {
+        if candidate.0 < Self::worst_distance(best_points, point_limit) {
+            let position = best_points.partition_point(|(distance, _)| *distance <= candidate.0);
+            best_points.insert(position, candidate);
+            best_points.truncate(point_limit);
+        }
+    }
+
+    /*
+    Distance a new point has to beat in order to enter the candidate list.
+    Until the list is full every point is accepted.
+    */
+    fn worst_distance(best_points: &[(f32, &P)], point_limit: usize) -> f32 {
+        match best_points.last() {
+            Some((distance, _)) if best_points.len() >= point_limit => *distance,
+            _ => f32::INFINITY
         }
     }
 }
@@ -309,8 +795,10 @@ impl<P> IKDTree<P> for KDTree<P>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tree::test_helpers::{brute_force_distances, distances_of, sorted};
 
     #[test]
+    #[allow(redundant_semicolons, clippy::bool_assert_comparison)]
     fn test_compare_points() {
         let point_a = Point3D::new(1.0, 2.0, 3.0);
         let point_b = Point3D::new(2.0, 2.0, 3.0);
@@ -333,20 +821,27 @@ mod tests {
     }
 
     #[test]
-    fn test_operation_point_list() {
-        let mut points = vec![
-            Point3D::new(1.0, 2.0, 3.0),
-            Point3D::new(4.0, 5.0, 6.0),
-            Point3D::new(7.0, 8.0, 9.0),
-        ];
+    fn test_create_kd_tree_rejects_invalid_k() {
+        let mut points = Point3D::generate_points(1_000, 0.0, 10.0);
 
-        let median = points.len() / 2;
+        assert!(KDTree::create_kd_tree(&mut points, 0, 0).is_err());
+        assert!(KDTree::create_kd_tree(&mut points, 0, 4).is_err());
+        assert!(KDTree::create_kd_tree(&mut points, 0, 2).is_ok());
+        assert!(KDTree::create_kd_tree(&mut Vec::<Point3D>::new(), 0, 3).is_err());
 
-        let left_points = KDTree::operation_point_list(&points, median, NodeDirection::LEFT);
-        let right_points = KDTree::operation_point_list(&points, median, NodeDirection::RIGHT);
+        assert!(KDTree::init(points.clone(), 0, 0).is_err());
+        assert!(KDTree::init(Vec::<Point3D>::new(), 3, 0).is_err());
+    }
 
-        assert_eq!(left_points, &points[..median]);
-        assert_eq!(right_points, &points[median+1..]);
+    #[test]
+    fn test_init_arranges_points() {
+        let points = Point3D::generate_points(1_000, 0.0, 10.0);
+        let tree = KDTree::init(points.clone(), 3, 0).unwrap();
+        assert_partitioned(tree.root());
+
+        let query_point = Point3D::new(5.0, 5.0, 5.0);
+        let expected = brute_force_distances(&points, &query_point);
+        assert_eq!(distances_of(&tree.find_closest(&query_point, 3, 10).unwrap()), &expected[..10]);
     }
 
     #[test]
@@ -361,41 +856,66 @@ mod tests {
         ];
 
 
-        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
-        root.point.as_ref().map(|point| assert_eq!(point, &Rc::new(Point3D::new(5.0, 6.0, 7.0))));
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let root = tree.root();
+        assert_eq!(root.point(), &Point3D::new(5.0, 6.0, 7.0));
+        assert_eq!(root.size(), 6);
 
-        if let Some(right) = &root.right {
-           right.point.as_ref().map(
-               |point| assert_eq!(point, &Rc::new(Point3D::new(8.0, 9.0, 10.0)))
-           );
+        let right = root.right().unwrap();
+        assert_eq!(right.point(), &Point3D::new(8.0, 9.0, 10.0));
+        assert_eq!(right.left().unwrap().point(), &Point3D::new(7.0, 8.0, 9.0));
+        assert!(right.right().is_none());
 
-            right.left.as_ref().map(
-                |leftt|
-                    leftt.point.as_ref().map(
-                        |point| assert_eq!(point, &Rc::new(Point3D::new(7.0, 8.0, 9.0)))
-                    )
-            );
-        }
+        let left = root.left().unwrap();
+        assert_eq!(left.point(), &Point3D::new(2.0, 3.0, 4.0));
+        assert_eq!(left.axis(), 1);
+        assert_eq!(left.left().unwrap().point(), &Point3D::new(1.0, 2.0, 3.0));
+        assert_eq!(left.right().unwrap().point(), &Point3D::new(4.0, 5.0, 6.0));
+        assert!(left.right().unwrap().is_leaf());
+
+        // Every node of the tree lives in the same contiguous array.
+        assert_eq!(tree.points().len(), 6);
+        assert_eq!(&tree.points()[3], root.point());
+
+        let bounds = root.bounds();
+        assert_eq!(bounds.min, &[1.0, 2.0, 3.0]);
+        assert_eq!(bounds.max, &[8.0, 9.0, 10.0]);
+        assert_eq!(left.bounds().max, &[4.0, 5.0, 6.0]);
+    }
 
-        if let Some(left) = &root.left {
-            left.point.as_ref().map(
-                |point| assert_eq!(point, &Rc::new(Point3D::new(2.0, 3.0, 4.0)))
-            );
+    fn assert_partitioned(node: Node<'_, Point3D>) {
+        let axis = node.axis();
+        let value = node.point().get_coordinate_at(axis);
 
-            left.left.as_ref().map(
-                |leftt|
-                    leftt.point.as_ref().map(
-                        |point| assert_eq!(point, &Rc::new(Point3D::new(1.0, 2.0, 3.0)))
-                    )
-            );
+        if let Some(left) = node.left() {
+            let tree_points = &left.tree.points[left.start..left.end];
+            assert!(tree_points.iter().all(|point| point.get_coordinate_at(axis) <= value));
+            assert_partitioned(left);
+        }
+        if let Some(right) = node.right() {
+            let tree_points = &right.tree.points[right.start..right.end];
+            assert!(tree_points.iter().all(|point| point.get_coordinate_at(axis) >= value));
+            assert_partitioned(right);
+        }
+    }
 
-            left.right.as_ref().map(
-                |right|
-                    right.point.as_ref().map(
-                        |point| assert_eq!(point, &Rc::new(Point3D::new(4.0, 5.0, 6.0)))
-                    )
-            );
+    #[test]
+    fn test_build_kd_tree_partitions_in_place() {
+        // Few distinct values, so a lot of points tie with the median.
+        let mut points = Point3D::generate_points(5_000, 0.0, 0.1);
+        let mut expected = points.clone();
+        let tree = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        assert_eq!(tree.root().size(), 5_000);
+        assert_partitioned(tree.root());
+
+        // The caller's points are arranged the same way, none is lost or duplicated.
+        assert_eq!(points, tree.points());
+        let mut arranged = points.clone();
+        for list in [&mut expected, &mut arranged] {
+            list.sort_by(|a, b| (a.x, a.y, a.z).partial_cmp(&(b.x, b.y, b.z)).unwrap());
         }
+        assert_eq!(arranged, expected);
     }
 
     #[test]
@@ -425,4 +945,332 @@ mod tests {
         assert_eq!(best_points[0].1, &Point3D::new(1.0, 1.0, 1.0));
         assert_eq!(best_points[1].1, &Point3D::new(2.0, 2.0, 2.0));
     }
+
+    #[test]
+    fn test_find_closest_matches_brute_force() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let point_limit = 10;
+        for _ in 0..50 {
+            // Query points are generated both inside and far outside of the data.
+            let query_point = Point3D::random_point(-20.0, 30.0);
+            let result = root.find_closest(&query_point, 3, point_limit).unwrap();
+
+            let expected = brute_force_distances(&all_points, &query_point);
+
+            assert_eq!(distances_of(&result), &expected[..point_limit]);
+        }
+    }
+
+    #[test]
+    fn test_find_closest_limit_above_tree_size() {
+        let mut points = vec![
+            Point3D::new(1.0, 1.0, 1.0),
+            Point3D::new(2.0, 2.0, 2.0),
+            Point3D::new(3.0, 3.0, 3.0),
+        ];
+
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        let best_points = root.find_closest(&Point3D::new(9.0, 9.0, 9.0), 3, 10).unwrap();
+
+        assert_eq!(best_points.len(), 3);
+        assert_eq!(best_points[0].1, &Point3D::new(3.0, 3.0, 3.0));
+        assert_eq!(best_points[2].1, &Point3D::new(1.0, 1.0, 1.0));
+        assert!(root.find_closest(&Point3D::new(9.0, 9.0, 9.0), 3, 0).is_none());
+    }
+
+    #[test]
+    fn test_within_radius() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        for radius in [0.0, 0.5, 1.5, 4.0] {
+            let query_point = Point3D::random_point(0.0, 10.0);
+            let result = root.within_radius(&query_point, radius);
+
+            let expected: Vec<f32> = brute_force_distances(&all_points, &query_point).into_iter()
+                .take_while(|distance| *distance <= radius)
+                .collect();
+
+            assert_eq!(distances_of(&result), expected);
+        }
+
+        let exact_hit = root.within_radius(&all_points[7], 0.0);
+        assert!(exact_hit.iter().any(|(_, point)| **point == all_points[7]));
+    }
+
+    #[test]
+    fn test_within_box() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let min_point = Point3D::new(2.0, 3.0, 1.0);
+        let max_point = Point3D::new(5.0, 4.5, 9.0);
+        let is_inside = |p: &Point3D| {
+            min_point.x <= p.x && p.x <= max_point.x &&
+                min_point.y <= p.y && p.y <= max_point.y &&
+                min_point.z <= p.z && p.z <= max_point.z
+        };
+
+        let result = root.within_box(&min_point, &max_point);
+        assert_eq!(result.len(), all_points.iter().filter(|p| is_inside(p)).count());
+        assert!(result.iter().all(|p| is_inside(p)));
+
+        let mut visited = 0;
+        root.visit_box(&min_point, &max_point, |_| visited += 1);
+        assert_eq!(visited, result.len());
+
+        let empty = root.within_box(&Point3D::new(20.0, 20.0, 20.0), &Point3D::new(30.0, 30.0, 30.0));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_find_closest_approx() {
+        let mut points = Point3D::generate_points(5_000, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let point_limit = 10;
+        let epsilon = 0.5;
+        for _ in 0..50 {
+            let query_point = Point3D::random_point(-5.0, 15.0);
+            let exact = root.find_closest(&query_point, 3, point_limit).unwrap();
+            let approx = root.find_closest_approx(&query_point, point_limit, epsilon).unwrap();
+            let zero_epsilon = root.find_closest_approx(&query_point, point_limit, 0.0).unwrap();
+
+            assert_eq!(approx.len(), point_limit);
+            for ((approx_point, exact_point), zero_point) in approx.iter().zip(&exact).zip(&zero_epsilon) {
+                assert!(approx_point.0 <= exact_point.0 * (1.0 + epsilon));
+                assert_eq!(zero_point.0, exact_point.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_closest_bbf() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let point_limit = 10;
+        for _ in 0..20 {
+            let query_point = Point3D::random_point(-5.0, 15.0);
+            let exact = root.find_closest(&query_point, 3, point_limit).unwrap();
+
+            // A budget covering the whole tree gives the exact answer.
+            let unbounded = root.find_closest_bbf(&query_point, point_limit, usize::MAX).unwrap();
+            assert_eq!(distances_of(&unbounded), distances_of(&exact));
+
+            // Every visited node computes one distance, so a budget of 5 finds at most 5 points.
+            let limited = root.find_closest_bbf(&query_point, point_limit, 5).unwrap();
+            assert_eq!(limited.len(), 5);
+            assert!(limited[0].0 >= exact[0].0);
+        }
+
+        assert!(root.find_closest_bbf(&Point3D::new(0.0, 0.0, 0.0), point_limit, 0).is_none());
+    }
+
+    #[test]
+    fn test_find_closest_batch() {
+        fn assert_thread_safe<T: Send + Sync>() {}
+        assert_thread_safe::<KDTree<Point3D>>();
+
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let queries = Point3D::generate_points(200, -5.0, 15.0);
+        let results = root.find_closest_batch(&queries, 10);
+
+        assert_eq!(results.len(), queries.len());
+        for (query_point, result) in queries.iter().zip(results) {
+            assert_eq!(result, root.find_closest(query_point, 3, 10));
+        }
+
+        assert!(root.find_closest_batch(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_find_farthest() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let point_limit = 5;
+        for _ in 0..20 {
+            let query_point = Point3D::random_point(-5.0, 15.0);
+            let result = root.find_farthest(&query_point, point_limit).unwrap();
+
+            let expected: Vec<f32> = brute_force_distances(&all_points, &query_point).into_iter().rev().collect();
+
+            assert_eq!(distances_of(&result), &expected[..point_limit]);
+        }
+
+        assert_eq!(root.find_farthest(&Point3D::new(0.0, 0.0, 0.0), 5_000).unwrap().len(), 2_000);
+        assert!(root.find_farthest(&Point3D::new(0.0, 0.0, 0.0), 0).is_none());
+    }
+
+    #[test]
+    fn test_count_queries() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+        assert_eq!(root.root().size(), 2_000);
+
+        for radius in [0.0, 1.0, 3.5, 100.0] {
+            let query_point = Point3D::random_point(0.0, 10.0);
+            assert_eq!(root.count_within_radius(&query_point, radius), root.within_radius(&query_point, radius).len());
+        }
+
+        let min_point = Point3D::new(1.0, 2.0, 0.5);
+        let max_point = Point3D::new(6.0, 4.0, 9.0);
+        assert_eq!(root.count_in_box(&min_point, &max_point), root.within_box(&min_point, &max_point).len());
+
+        let everything = root.count_in_box(&Point3D::new(-1.0, -1.0, -1.0), &Point3D::new(11.0, 11.0, 11.0));
+        assert_eq!(everything, 2_000);
+    }
+
+    #[test]
+    fn test_find_closest_where() {
+        let mut points = Point3D::generate_points(2_000, -5.0, 5.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        // The query sits on the rejected side, so nearby points are all filtered out.
+        let query_point = Point3D::new(0.0, 0.0, -4.0);
+        let result = root.find_closest_where(&query_point, 10, |p| p.z > 0.0).unwrap();
+
+        let accepted: Vec<Point3D> = all_points.iter().filter(|p| p.z > 0.0).copied().collect();
+        let expected = brute_force_distances(&accepted, &query_point);
+
+        assert_eq!(result.len(), 10);
+        assert!(result.iter().all(|(_, p)| p.z > 0.0));
+        assert_eq!(distances_of(&result), &expected[..10]);
+
+        assert!(root.find_closest_where(&query_point, 10, |p| p.z > 100.0).is_none());
+    }
+
+    #[test]
+    fn test_find_closest_to_line() {
+        let mut points = Point3D::generate_points(2_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let origin = Point3D::new(-5.0, 2.0, 3.0);
+        let direction = Point3D::new(1.0, 0.5, 0.2);
+        let lines = [
+            Line::ray(&origin, &direction),
+            Line::segment(&origin, &Point3D::new(1.0, 3.0, 3.0)),
+            Line::infinite(&Point3D::new(5.0, 5.0, 5.0), &Point3D::new(0.0, 0.0, 1.0)),
+        ];
+
+        for line in &lines {
+            let result = root.find_closest_to_line(line, 10).unwrap();
+
+            let expected = sorted(all_points.iter().map(|p| line.distance_to(&[p.x, p.y, p.z])).collect());
+
+            assert_eq!(distances_of(&result), &expected[..10]);
+        }
+    }
+
+    #[test]
+    fn test_index_of() {
+        let mut points = Point3D::generate_points(100, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        for (index, point) in root.points().iter().enumerate() {
+            assert_eq!(root.index_of(point), index);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "point is not stored in this tree")]
+    fn test_index_of_foreign_point() {
+        let mut points = Point3D::generate_points(100, 0.0, 10.0);
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        root.index_of(&points[0]);
+    }
+
+    #[test]
+    fn test_find_closest_excluding() {
+        let mut points = vec![
+            Point3D::new(1.0, 1.0, 1.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            Point3D::new(2.0, 2.0, 2.0),
+            Point3D::new(4.0, 4.0, 4.0),
+        ];
+        let root = KDTree::create_kd_tree(&mut points, 0, 3).unwrap();
+
+        let stored = root.traverse(TraversalOrder::PreOrder)
+            .map(|visit| visit.point)
+            .find(|point| point.x == 1.0)
+            .unwrap();
+
+        // The duplicate is a legitimate neighbour at distance 0, the stored point itself is not.
+        let result = root.find_closest_excluding(stored, 3, stored).unwrap();
+        assert_eq!(result.len(), 3);
+        assert!(result.iter().all(|(_, point)| !std::ptr::eq(*point, stored)));
+        assert_eq!(result[0].0, 0.0);
+        assert_eq!(result[0].1, &Point3D::new(1.0, 1.0, 1.0));
+        assert_eq!(result[1].1, &Point3D::new(2.0, 2.0, 2.0));
+
+        // A copy of the point is not stored in the tree, so nothing is excluded.
+        let copy = *stored;
+        assert_eq!(root.find_closest_excluding(&copy, 4, &copy).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_queries_on_tree_with_two_axes() {
+        let mut points = Point3D::generate_points(3_000, 0.0, 10.0);
+        let all_points = points.clone();
+        let root = KDTree::create_kd_tree(&mut points, 0, 2).unwrap();
+
+        // The z axis is never used to split, whatever the dimension of the points is.
+        assert!(root.traverse(TraversalOrder::PreOrder).all(|visit| visit.axis == visit.depth % 2));
+
+        let queries = Point3D::generate_points(50, -2.0, 12.0);
+        for query_point in &queries {
+            let expected = brute_force_distances(&all_points, query_point);
+
+            assert_eq!(distances_of(&root.find_closest(query_point, 2, 10).unwrap()), &expected[..10]);
+            assert_eq!(distances_of(&root.find_closest_approx(query_point, 10, 0.0).unwrap()), &expected[..10]);
+            assert_eq!(root.find_closest(query_point, 3, 10), root.find_closest(query_point, 2, 10));
+            assert_eq!(distances_of(&root.find_closest_where(query_point, 10, |_| true).unwrap()), &expected[..10]);
+            assert_eq!(distances_of(&root.find_closest_bbf(query_point, 10, usize::MAX).unwrap()), &expected[..10]);
+
+            let farthest: Vec<f32> = expected.iter().rev().take(10).copied().collect();
+            assert_eq!(distances_of(&root.find_farthest(query_point, 10).unwrap()), farthest);
+
+            let within: Vec<f32> = expected.iter().copied().take_while(|distance| *distance <= 2.0).collect();
+            assert_eq!(distances_of(&root.within_radius(query_point, 2.0)), within);
+            assert_eq!(root.count_within_radius(query_point, 2.0), within.len());
+
+            let nearest: Vec<f32> = root.nearest_iter(query_point).map(|(distance, _)| distance).collect();
+            assert_eq!(nearest, expected);
+
+            let min_point = Point3D::new(query_point.x - 1.5, query_point.y - 2.0, query_point.z - 3.0);
+            let max_point = Point3D::new(query_point.x + 2.0, query_point.y + 1.0, query_point.z + 3.0);
+            let in_box = all_points.iter()
+                .filter(|p| min_point.x <= p.x && p.x <= max_point.x)
+                .filter(|p| min_point.y <= p.y && p.y <= max_point.y)
+                .filter(|p| min_point.z <= p.z && p.z <= max_point.z)
+                .count();
+            assert_eq!(root.within_box(&min_point, &max_point).len(), in_box);
+            assert_eq!(root.count_in_box(&min_point, &max_point), in_box);
+        }
+
+        let batch = root.find_closest_batch(&queries, 5);
+        for (query_point, result) in queries.iter().zip(batch) {
+            assert_eq!(result, root.find_closest(query_point, 2, 5));
+        }
+
+        let stored = root.root().point();
+        let expected = brute_force_distances(&all_points, stored);
+        assert_eq!(distances_of(&root.find_closest_excluding(stored, 5, stored).unwrap()), &expected[1..6]);
+
+        let line = Line::infinite(&Point3D::new(5.0, 5.0, 5.0), &Point3D::new(0.0, 0.0, 1.0));
+        let expected = sorted(all_points.iter().map(|p| line.distance_to(&[p.x, p.y, p.z])).collect());
+        assert_eq!(distances_of(&root.find_closest_to_line(&line, 10).unwrap()), &expected[..10]);
+    }
 }
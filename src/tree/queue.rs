@@ -0,0 +1,57 @@
+use std::cmp::Ordering;
+
+/*
+Entry of a min-priority queue keyed by distance.
+std BinaryHeap is a max-heap and f32 is not Ord, so ordering is reversed and
+compared with total_cmp here, which makes the nearest entry pop first.
+*/
+#[derive(Debug)]
+pub struct QueueItem<T> {
+    pub distance: f32,
+    pub item: T,
+}
+
+impl<T> QueueItem<T> {
+    pub fn new(distance: f32, item: T) -> Self {
+        QueueItem { distance, item }
+    }
+}
+
+impl<T> PartialEq for QueueItem<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance.total_cmp(&other.distance) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for QueueItem<T> {}
+
+impl<T> PartialOrd for QueueItem<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for QueueItem<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BinaryHeap;
+    use super::*;
+
+    #[test]
+    fn test_nearest_pops_first() {
+        let mut queue = BinaryHeap::new();
+        queue.push(QueueItem::new(3.0, "c"));
+        queue.push(QueueItem::new(1.0, "a"));
+        queue.push(QueueItem::new(2.0, "b"));
+
+        assert_eq!(queue.pop().unwrap().item, "a");
+        assert_eq!(queue.pop().unwrap().item, "b");
+        assert_eq!(queue.pop().unwrap().item, "c");
+        assert!(queue.pop().is_none());
+    }
+}
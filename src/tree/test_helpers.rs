@@ -0,0 +1,17 @@
+use crate::points::point::{Point, Point3D};
+
+/* Distances in increasing order. */
+pub fn sorted(mut distances: Vec<f32>) -> Vec<f32> {
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distances
+}
+
+/* Distance from query_point to every point, in increasing order. */
+pub fn brute_force_distances(points: &[Point3D], query_point: &Point3D) -> Vec<f32> {
+    sorted(points.iter().map(|p| query_point.distance_to(p)).collect())
+}
+
+/* Distances of a query result, in the order of the result. */
+pub fn distances_of<P>(result: &[(f32, &P)]) -> Vec<f32> {
+    result.iter().map(|(distance, _)| *distance).collect()
+}